@@ -0,0 +1,255 @@
+//! Groq LLM integration: ignore suggestions and query-driven file selection.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    env, io,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::display_path;
+
+// Groq API structures
+#[derive(Serialize)]
+struct GroqMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Serialize)]
+struct GroqRequest {
+    messages: Vec<GroqMessage>,
+    model: String,
+    temperature: f32,
+    max_completion_tokens: u32,
+    top_p: f32,
+    stream: bool,
+    reasoning_effort: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GroqStreamChoice {
+    delta: GroqDelta,
+}
+
+#[derive(Deserialize, Debug)]
+struct GroqDelta {
+    content: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GroqStreamResponse {
+    choices: Vec<GroqStreamChoice>,
+}
+
+/// Directories to skip during the initial scan (these are almost always noise)
+const SCAN_SKIP_DIRS: &[&str] = &[
+    "node_modules",
+    ".git",
+    ".hg",
+    ".svn",
+    "target",
+    "__pycache__",
+    ".venv",
+    "venv",
+    "env",
+    ".env",
+    "dist",
+    "build",
+    ".next",
+    ".nuxt",
+    "vendor",
+    ".cargo",
+    "deps",
+    ".deps",
+];
+
+/// Scans a directory and collects all unique file extensions and directory names
+/// Only scans top 3 levels and skips known dependency/build directories
+pub fn collect_extensions_and_dirs(dir: &Path) -> (HashSet<String>, HashSet<String>) {
+    let mut extensions: HashSet<String> = HashSet::new();
+    let mut directories: HashSet<String> = HashSet::new();
+
+    let skip_dirs: HashSet<&str> = SCAN_SKIP_DIRS.iter().copied().collect();
+
+    for entry in WalkDir::new(dir)
+        .max_depth(3) // Only scan top 3 levels
+        .into_iter()
+        .filter_entry(|e| {
+            // Skip known problematic directories
+            if e.file_type().is_dir() {
+                if let Some(name) = e.file_name().to_str() {
+                    let name_lower = name.to_lowercase();
+                    return !skip_dirs.contains(name_lower.as_str());
+                }
+            }
+            true
+        })
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+
+        if path.is_dir() {
+            if let Some(dirname) = path.file_name() {
+                let name = dirname.to_string_lossy().to_lowercase();
+                // Skip hidden directories from collection (but we still add them for AI to consider)
+                if !name.is_empty() {
+                    directories.insert(name);
+                }
+            }
+        } else if path.is_file() {
+            if let Some(ext) = path.extension() {
+                let ext_str = ext.to_string_lossy().to_lowercase();
+                if !ext_str.is_empty() {
+                    extensions.insert(ext_str);
+                }
+            }
+        }
+    }
+
+    (extensions, directories)
+}
+
+/// Sends a single prompt to the Groq chat completions API and returns the streamed reply.
+/// Returns `Ok(None)` when GROQ_API_KEY is not set.
+pub fn groq_complete(prompt: String) -> io::Result<Option<String>> {
+    let api_key = match env::var("GROQ_API_KEY") {
+        Ok(key) => key,
+        Err(_) => return Ok(None),
+    };
+
+    let client = reqwest::blocking::Client::new();
+
+    let request = GroqRequest {
+        messages: vec![GroqMessage {
+            role: "user".to_string(),
+            content: prompt,
+        }],
+        model: "qwen/qwen3-32b".to_string(),
+        temperature: 0.6,
+        max_completion_tokens: 4096,
+        top_p: 0.95,
+        stream: true,
+        reasoning_effort: "default".to_string(),
+    };
+
+    let response = client
+        .post("https://api.groq.com/openai/v1/chat/completions")
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&request)
+        .send()
+        .map_err(|e| io::Error::other(format!("HTTP request failed: {}", e)))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        return Err(io::Error::other(format!(
+            "Groq API error ({}): {}",
+            status, body
+        )));
+    }
+
+    // Process streaming response
+    let mut full_content = String::new();
+
+    for line in response.text().unwrap_or_default().lines() {
+        let line = line.trim();
+        if line.is_empty() || line == "data: [DONE]" {
+            continue;
+        }
+
+        if let Some(json_str) = line.strip_prefix("data: ") {
+            if let Ok(stream_response) = serde_json::from_str::<GroqStreamResponse>(json_str) {
+                for choice in stream_response.choices {
+                    if let Some(content) = choice.delta.content {
+                        full_content.push_str(&content);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(Some(full_content))
+}
+
+/// Whether GROQ_API_KEY is set, without which the AI functions do nothing
+pub fn has_api_key() -> bool {
+    env::var("GROQ_API_KEY").is_ok()
+}
+
+/// Extracts a JSON array of strings from an LLM reply, which may contain extra text around it
+fn parse_json_string_array(full_content: &str) -> io::Result<Vec<String>> {
+    let json_start = full_content.find('[');
+    let json_end = full_content.rfind(']');
+
+    match (json_start, json_end) {
+        (Some(start), Some(end)) if end > start => {
+            let json_str = &full_content[start..=end];
+            serde_json::from_str(json_str).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Failed to parse AI response as JSON: {}", e),
+                )
+            })
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Could not find JSON array in AI response",
+        )),
+    }
+}
+
+/// Calls the Groq LLM API to get suggestions for what to ignore. Returns nothing when
+/// GROQ_API_KEY is not set.
+pub fn get_ai_ignore_suggestions(
+    extensions: &HashSet<String>,
+    directories: &HashSet<String>,
+) -> io::Result<Vec<String>> {
+    // Build the list of items to send to the LLM
+    let mut items: Vec<String> = Vec::new();
+
+    for ext in extensions {
+        items.push(format!(".{}", ext));
+    }
+    for dir in directories {
+        items.push(dir.clone());
+    }
+
+    if items.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let prompt = format!(
+        "I am filtering a codebase with the following directories and file extensions for only files that are useful in understanding the function of the application. Which of these should I ignore? Send only a JSON array of strings back and nothing else.\n\n{}",
+        items.join(", ")
+    );
+
+    match groq_complete(prompt)? {
+        Some(reply) => parse_json_string_array(&reply),
+        None => Ok(vec![]),
+    }
+}
+
+/// Asks the Groq LLM API which of the collected files are relevant to `question`, most relevant first
+pub fn get_ai_relevant_files(question: &str, files: &[(PathBuf, u64)]) -> io::Result<Vec<String>> {
+    let tree: Vec<String> = files
+        .iter()
+        .map(|(path, size)| format!("{} ({} bytes)", display_path(path), size))
+        .collect();
+
+    let prompt = format!(
+        "Below is the file list of a codebase with file sizes. I want to answer this question about the codebase: \"{}\"\n\nWhich files should I read to answer it? Rank them from most to least relevant and only include files that are likely to matter. Send only a JSON array of file paths exactly as written below and nothing else.\n\n{}",
+        question,
+        tree.join("\n")
+    );
+
+    match groq_complete(prompt)? {
+        Some(reply) => parse_json_string_array(&reply),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--query requires GROQ_API_KEY to be set",
+        )),
+    }
+}
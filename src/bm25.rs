@@ -0,0 +1,237 @@
+//! Offline lexical relevance ranking with BM25.
+//!
+//! Documents are tokenised on identifier boundaries, so `parseHttpRequest` and
+//! `parse_http_request` both yield `parse`, `http` and `request` (plus the whole identifier).
+
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Splits text into lowercase terms, breaking identifiers on camelCase and snake_case boundaries
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+
+    for word in text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| !w.is_empty())
+    {
+        let parts = split_identifier(word);
+        if parts.len() > 1 {
+            let whole = word.trim_matches('_').to_lowercase();
+            if whole.len() > 1 {
+                terms.push(whole);
+            }
+        }
+        terms.extend(parts.into_iter().filter(|p| p.len() > 1));
+    }
+
+    terms
+}
+
+fn split_identifier(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+
+    for chunk in word.split('_').filter(|c| !c.is_empty()) {
+        let chars: Vec<char> = chunk.chars().collect();
+        let mut current = String::new();
+
+        for (i, &c) in chars.iter().enumerate() {
+            // Break before an uppercase letter that follows a lowercase one (`fooBar`),
+            // or that starts a new word after an acronym (`HTTPServer` -> `HTTP`, `Server`)
+            let boundary = c.is_uppercase()
+                && i > 0
+                && (chars[i - 1].is_lowercase()
+                    || chars[i - 1].is_numeric()
+                    || (chars[i - 1].is_uppercase()
+                        && chars.get(i + 1).is_some_and(|n| n.is_lowercase())));
+            if boundary && !current.is_empty() {
+                parts.push(current.to_lowercase());
+                current.clear();
+            }
+            current.push(c);
+        }
+
+        if !current.is_empty() {
+            parts.push(current.to_lowercase());
+        }
+    }
+
+    parts
+}
+
+struct Document {
+    path: PathBuf,
+    term_freqs: HashMap<String, u32>,
+    length: usize,
+}
+
+/// In-memory BM25 index over a set of files
+pub struct Index {
+    documents: Vec<Document>,
+    doc_freqs: HashMap<String, usize>,
+    avg_length: f64,
+}
+
+impl Index {
+    /// Reads and tokenises `files` in parallel. The path is indexed along with the content.
+    /// Unreadable files are indexed by path only.
+    pub fn build(files: &[PathBuf]) -> Self {
+        let documents: Vec<Document> = files
+            .par_iter()
+            .map(|path| {
+                let content = fs::read(path)
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                    .unwrap_or_default();
+
+                let mut term_freqs: HashMap<String, u32> = HashMap::new();
+                let mut length = 0;
+                for term in tokenize(&path.to_string_lossy())
+                    .into_iter()
+                    .chain(tokenize(&content))
+                {
+                    *term_freqs.entry(term).or_insert(0) += 1;
+                    length += 1;
+                }
+
+                Document {
+                    path: path.clone(),
+                    term_freqs,
+                    length,
+                }
+            })
+            .collect();
+
+        let mut doc_freqs: HashMap<String, usize> = HashMap::new();
+        for doc in &documents {
+            for term in doc.term_freqs.keys() {
+                *doc_freqs.entry(term.clone()).or_insert(0) += 1;
+            }
+        }
+
+        let total_length: usize = documents.iter().map(|d| d.length).sum();
+        let avg_length = if documents.is_empty() {
+            0.0
+        } else {
+            total_length as f64 / documents.len() as f64
+        };
+
+        Self {
+            documents,
+            doc_freqs,
+            avg_length,
+        }
+    }
+
+    /// Scores every document against `query` and returns those that match, best first
+    pub fn search(&self, query: &str) -> Vec<(&Path, f64)> {
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        let total_docs = self.documents.len() as f64;
+        let mut results: Vec<(&Path, f64)> = self
+            .documents
+            .iter()
+            .filter_map(|doc| {
+                let score: f64 = terms
+                    .iter()
+                    .filter_map(|term| {
+                        let tf = *doc.term_freqs.get(term)? as f64;
+                        let df = self.doc_freqs[term] as f64;
+                        let idf = ((total_docs - df + 0.5) / (df + 0.5) + 1.0).ln();
+                        let norm = K1 * (1.0 - B + B * doc.length as f64 / self.avg_length);
+                        Some(idf * tf * (K1 + 1.0) / (tf + norm))
+                    })
+                    .sum();
+                (score > 0.0).then_some((doc.path.as_path(), score))
+            })
+            .collect();
+
+        results.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_camel_and_snake_case() {
+        assert_eq!(
+            split_identifier("parseHttpRequest"),
+            ["parse", "http", "request"]
+        );
+        assert_eq!(
+            split_identifier("parse_http_request"),
+            ["parse", "http", "request"]
+        );
+        assert_eq!(split_identifier("__init__"), ["init"]);
+    }
+
+    #[test]
+    fn splits_acronyms_before_the_next_word() {
+        assert_eq!(split_identifier("HTTPServer"), ["http", "server"]);
+        assert_eq!(split_identifier("parseJSON"), ["parse", "json"]);
+        assert_eq!(
+            split_identifier("XMLHttpRequest"),
+            ["xml", "http", "request"]
+        );
+    }
+
+    #[test]
+    fn splits_after_digits() {
+        assert_eq!(split_identifier("utf8Decode"), ["utf8", "decode"]);
+        assert_eq!(split_identifier("sha256"), ["sha256"]);
+    }
+
+    #[test]
+    fn tokenize_keeps_the_whole_identifier_and_drops_single_letters() {
+        assert_eq!(
+            tokenize("fn parseToken(a: &str)"),
+            ["fn", "parsetoken", "parse", "token", "str"]
+        );
+        assert_eq!(tokenize("x = y"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn identifier_styles_match_each_other() {
+        // Paths that don't exist are indexed by path only
+        let files = [
+            PathBuf::from("src/auth/session_token.rs"),
+            PathBuf::from("src/render/template.rs"),
+            PathBuf::from("src/auth/login.rs"),
+        ];
+        let index = Index::build(&files);
+
+        let results = index.search("sessionToken");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, Path::new("src/auth/session_token.rs"));
+    }
+
+    #[test]
+    fn ranks_rarer_terms_higher() {
+        let files = [
+            PathBuf::from("auth/login.rs"),
+            PathBuf::from("auth/session.rs"),
+            PathBuf::from("auth/session_store.rs"),
+            PathBuf::from("render/output.rs"),
+        ];
+        let index = Index::build(&files);
+
+        let paths: Vec<&Path> = index
+            .search("auth login")
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(paths[0], Path::new("auth/login.rs"));
+        assert_eq!(paths.len(), 3);
+        assert!(index.search("missing").is_empty());
+    }
+}
@@ -0,0 +1,410 @@
+//! On-disk cache of rendered file blocks, so repeated runs only reprocess changed files.
+//!
+//! Each entry is keyed by the file path and the render settings, and records the file's size,
+//! mtime and content hash. A matching size and mtime is a hit without reading the file; otherwise
+//! the file is read and a matching content hash is still a hit.
+//!
+//! Entries live in one directory per repository and set of render settings. Opening the cache
+//! removes all but the most recently used settings of the repository, and repositories whose
+//! cache hasn't been used in a while, so changing flags or commits doesn't grow it forever.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Sets of render settings kept per repository, so switching between a few doesn't start over
+const KEPT_FINGERPRINTS: usize = 4;
+
+/// Caches of repositories not rendered for this long are removed
+const MAX_UNUSED_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// File whose mtime records when a settings directory was last used
+const LAST_USED: &str = "last_used";
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    mtime_nanos: Option<u128>,
+    content_hash: String,
+    tokens: u64,
+    block: String,
+}
+
+/// A rendered file block and its estimated token count
+pub(crate) struct RenderedBlock {
+    pub block: Vec<u8>,
+    pub tokens: u64,
+}
+
+/// Cache directory for one repository and one set of render settings
+pub(crate) struct Cache {
+    dir: PathBuf,
+    /// The rendered block shows the mtime, so a touched file can't reuse it even if unchanged
+    mtime_in_output: bool,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// Default cache location: `$XDG_CACHE_HOME/repo_to_text`, falling back to `~/.cache/repo_to_text`
+pub fn default_cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+        .map(|dir| dir.join("repo_to_text"))
+}
+
+pub(crate) fn content_hash(content: &[u8]) -> String {
+    format!("{:x}", md5::compute(content))
+}
+
+fn mtime_nanos(modified: Option<SystemTime>) -> Option<u128> {
+    modified
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+}
+
+impl Cache {
+    /// Opens the cache for `root` under `base_dir`. `fingerprint` must change whenever the
+    /// rendered output for an unchanged file would change. `mtime_in_output` says whether
+    /// blocks show the file's mtime.
+    pub fn open(
+        base_dir: &Path,
+        root: &Path,
+        fingerprint: String,
+        mtime_in_output: bool,
+    ) -> io::Result<Self> {
+        // Keep each repository in its own directory so unrelated runs don't collide
+        let root = fs::canonicalize(root)?;
+        let repo_dir = base_dir.join(content_hash(root.as_os_str().as_encoded_bytes()));
+        let dir = repo_dir.join(content_hash(fingerprint.as_bytes()));
+        fs::create_dir_all(&dir)?;
+        fs::File::create(dir.join(LAST_USED))?.set_modified(SystemTime::now())?;
+
+        // Pruning failures only leave old entries behind, so they are ignored
+        prune_fingerprints(&repo_dir, &dir);
+        prune_repositories(base_dir, &repo_dir);
+
+        Ok(Self {
+            dir,
+            mtime_in_output,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        })
+    }
+
+    fn entry_path(&self, file: &Path) -> PathBuf {
+        // The raw bytes, so non-UTF-8 names that look alike after a lossy conversion don't share
+        // an entry
+        let key = content_hash(file.as_os_str().as_encoded_bytes());
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn read_entry(&self, file: &Path) -> Option<CacheEntry> {
+        let data = fs::read(self.entry_path(file)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    /// Returns the cached block if the file is unchanged. `read` is only called when the size
+    /// or mtime differs and the content hash has to be compared.
+    pub fn lookup(
+        &self,
+        file: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+        read: impl FnOnce() -> io::Result<Vec<u8>>,
+    ) -> io::Result<Result<RenderedBlock, Vec<u8>>> {
+        let mtime = mtime_nanos(modified);
+
+        let Some(mut entry) = self.read_entry(file) else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return Ok(Err(read()?));
+        };
+
+        if entry.size == size && mtime.is_some() && entry.mtime_nanos == mtime {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Ok(RenderedBlock {
+                block: entry.block.into_bytes(),
+                tokens: entry.tokens,
+            }));
+        }
+
+        let content = read()?;
+        if self.mtime_in_output || entry.content_hash != content_hash(&content) {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return Ok(Err(content));
+        }
+
+        // Touched but unchanged: refresh the stat fields so the next run skips the read
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        entry.size = size;
+        entry.mtime_nanos = mtime;
+        let _ = self.write_entry(file, &entry);
+
+        Ok(Ok(RenderedBlock {
+            block: entry.block.into_bytes(),
+            tokens: entry.tokens,
+        }))
+    }
+
+    /// Stores a freshly rendered block. Failures only cost a cache miss next time, so they are ignored.
+    pub fn store(
+        &self,
+        file: &Path,
+        size: u64,
+        modified: Option<SystemTime>,
+        content: &[u8],
+        rendered: &RenderedBlock,
+    ) {
+        let entry = CacheEntry {
+            size,
+            mtime_nanos: mtime_nanos(modified),
+            content_hash: content_hash(content),
+            tokens: rendered.tokens,
+            block: String::from_utf8_lossy(&rendered.block).into_owned(),
+        };
+        let _ = self.write_entry(file, &entry);
+    }
+
+    fn write_entry(&self, file: &Path, entry: &CacheEntry) -> io::Result<()> {
+        // Write then rename so a concurrent or interrupted run never sees a partial entry
+        let path = self.entry_path(file);
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp, serde_json::to_vec(entry)?)?;
+        fs::rename(&tmp, &path)
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// Removes the settings directories of `repo_dir` other than `current` and the most recently
+/// used ones, and entries left by versions that kept everything in `repo_dir` itself
+fn prune_fingerprints(repo_dir: &Path, current: &Path) {
+    let Ok(entries) = fs::read_dir(repo_dir) else {
+        return;
+    };
+
+    let mut others = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path == current {
+            continue;
+        }
+        if path.is_dir() {
+            if is_cache_name(&path) {
+                others.push((last_used(&path), path));
+            }
+        } else if path.extension().is_some_and(|ext| ext == "json") && is_cache_name(&path) {
+            let _ = fs::remove_file(&path);
+        }
+    }
+
+    others.sort_by_key(|(used, _)| std::cmp::Reverse(*used));
+    for (_, path) in others.into_iter().skip(KEPT_FINGERPRINTS - 1) {
+        let _ = fs::remove_dir_all(path);
+    }
+}
+
+/// Removes the caches of repositories other than `current` that haven't been used for
+/// [`MAX_UNUSED_AGE`], such as ones that were deleted or moved
+fn prune_repositories(base_dir: &Path, current: &Path) {
+    let Ok(entries) = fs::read_dir(base_dir) else {
+        return;
+    };
+    let Some(cutoff) = SystemTime::now().checked_sub(MAX_UNUSED_AGE) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path == current || !path.is_dir() || !is_cache_name(&path) {
+            continue;
+        }
+        let newest = fs::read_dir(&path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| last_used(&entry.path()))
+            .max()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        if newest < cutoff {
+            let _ = fs::remove_dir_all(&path);
+        }
+    }
+}
+
+/// When a settings directory was last opened, or the directory's own mtime if it wasn't marked
+fn last_used(dir: &Path) -> SystemTime {
+    fs::metadata(dir.join(LAST_USED))
+        .or_else(|_| fs::metadata(dir))
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Whether `path` is named like something this cache created (an MD5 hash), so pruning never
+/// touches other files in a shared `--cache-dir`
+fn is_cache_name(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.len() == 32 && stem.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory that is removed again when dropped
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!(
+                "repo_to_text-cache-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Creates a settings directory named like a hash, marked as last used `age` ago
+    fn settings_dir(parent: &Path, name: &str, age: Duration) -> PathBuf {
+        let dir = parent.join(content_hash(name.as_bytes()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::File::create(dir.join(LAST_USED))
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+        dir
+    }
+
+    fn block(text: &str) -> RenderedBlock {
+        RenderedBlock {
+            block: text.as_bytes().to_vec(),
+            tokens: 1,
+        }
+    }
+
+    #[test]
+    fn keeps_the_most_recently_used_settings() {
+        let scratch = Scratch::new("fingerprints");
+        let hour = Duration::from_secs(60 * 60);
+        let current = settings_dir(&scratch.0, "current", Duration::ZERO);
+        let others: Vec<PathBuf> = (1..=6)
+            .map(|i| settings_dir(&scratch.0, &format!("old{}", i), hour * i))
+            .collect();
+
+        prune_fingerprints(&scratch.0, &current);
+
+        assert!(current.is_dir());
+        for (i, dir) in others.iter().enumerate() {
+            assert_eq!(dir.is_dir(), i < KEPT_FINGERPRINTS - 1, "old{}", i + 1);
+        }
+    }
+
+    #[test]
+    fn pruning_leaves_other_names_alone() {
+        let scratch = Scratch::new("foreign");
+        let old = MAX_UNUSED_AGE * 2;
+        let repo_dir = scratch.0.join(content_hash(b"repo"));
+        let current = settings_dir(&repo_dir, "current", Duration::ZERO);
+
+        let legacy = repo_dir.join(format!("{}.json", content_hash(b"legacy")));
+        fs::write(&legacy, "{}").unwrap();
+        fs::write(repo_dir.join("notes.json"), "{}").unwrap();
+        for i in 0..KEPT_FINGERPRINTS + 2 {
+            fs::create_dir_all(repo_dir.join(format!("backup-{}", i))).unwrap();
+        }
+        let stale_repo = settings_dir(&scratch.0.join(content_hash(b"gone")), "settings", old);
+        let user_dir = scratch.0.join("projects");
+        fs::create_dir_all(&user_dir).unwrap();
+        fs::File::create(user_dir.join(LAST_USED))
+            .unwrap()
+            .set_modified(SystemTime::now() - old)
+            .unwrap();
+
+        prune_fingerprints(&repo_dir, &current);
+        prune_repositories(&scratch.0, &repo_dir);
+
+        assert!(!legacy.exists());
+        assert!(repo_dir.join("notes.json").is_file());
+        for i in 0..KEPT_FINGERPRINTS + 2 {
+            assert!(repo_dir.join(format!("backup-{}", i)).is_dir());
+        }
+        assert!(!stale_repo.parent().unwrap().exists());
+        assert!(user_dir.join(LAST_USED).is_file());
+        assert!(current.is_dir());
+    }
+
+    /// Stores `content` for a file, then looks it up again after it was touched
+    fn lookup_after_touch(name: &str, mtime_in_output: bool) -> (Cache, bool) {
+        let scratch = Scratch::new(name);
+        let cache =
+            Cache::open(&scratch.0, &scratch.0, "settings".into(), mtime_in_output).unwrap();
+        let file = Path::new("src/lib.rs");
+        let content = b"fn main() {}\n";
+        let before = SystemTime::now() - Duration::from_secs(60);
+        cache.store(file, 13, Some(before), content, &block("rendered"));
+
+        let hit = match cache.lookup(file, 13, Some(SystemTime::now()), || Ok(content.to_vec())) {
+            Ok(Ok(cached)) => {
+                assert_eq!(cached.block, b"rendered");
+                true
+            }
+            Ok(Err(read)) => {
+                assert_eq!(read, content);
+                false
+            }
+            Err(e) => panic!("{}", e),
+        };
+        (cache, hit)
+    }
+
+    #[test]
+    fn touched_but_unchanged_file_is_a_hit() {
+        let (cache, hit) = lookup_after_touch("touched", false);
+        assert!(hit);
+        assert_eq!((cache.hits(), cache.misses()), (1, 0));
+    }
+
+    #[test]
+    fn touched_file_is_a_miss_when_blocks_show_the_mtime() {
+        let (cache, hit) = lookup_after_touch("touched-mtime", true);
+        assert!(!hit);
+        assert_eq!((cache.hits(), cache.misses()), (0, 1));
+    }
+
+    #[test]
+    fn unchanged_size_and_mtime_is_a_hit_without_reading() {
+        let scratch = Scratch::new("unchanged");
+        let cache = Cache::open(&scratch.0, &scratch.0, "settings".into(), true).unwrap();
+        let file = Path::new("a.rs");
+        let modified = Some(SystemTime::now());
+        cache.store(file, 3, modified, b"abc", &block("a"));
+
+        let looked_up = cache.lookup(file, 3, modified, || panic!("read an unchanged file"));
+        assert!(matches!(looked_up, Ok(Ok(_))));
+
+        let looked_up = cache.lookup(file, 4, modified, || Ok(b"abcd".to_vec()));
+        assert!(matches!(looked_up, Ok(Err(_))));
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    }
+}
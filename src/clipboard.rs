@@ -0,0 +1,151 @@
+//! Copying the rendered output to the clipboard.
+
+use base64::Engine;
+use clap::ValueEnum;
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{self, Write},
+};
+
+/// How to reach the clipboard
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ClipboardMethod {
+    /// The system clipboard, or OSC 52 over SSH or when no clipboard is available
+    #[default]
+    Auto,
+    /// The system clipboard (X11, Wayland, macOS or Windows)
+    System,
+    /// An OSC 52 escape sequence, which the terminal copies even over SSH
+    Osc52,
+}
+
+/// Copies `text` to the clipboard and returns the method that was used
+pub fn copy(text: &str, method: ClipboardMethod) -> io::Result<ClipboardMethod> {
+    match method {
+        ClipboardMethod::System => copy_system(text).map(|_| ClipboardMethod::System),
+        ClipboardMethod::Osc52 => copy_osc52(text).map(|_| ClipboardMethod::Osc52),
+        ClipboardMethod::Auto => {
+            // A remote session's system clipboard is on the wrong machine
+            if env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some() {
+                return copy_osc52(text).map(|_| ClipboardMethod::Osc52);
+            }
+            match copy_system(text) {
+                Ok(()) => Ok(ClipboardMethod::System),
+                Err(_) => copy_osc52(text).map(|_| ClipboardMethod::Osc52),
+            }
+        }
+    }
+}
+
+/// Set in the environment of the child process that keeps serving the clipboard
+pub const DAEMON_ENV: &str = "REPO_TO_TEXT_CLIPBOARD_DAEMON";
+
+/// Whether this process was started to serve the clipboard; see [`run_daemon`]
+pub fn is_daemon() -> bool {
+    env::var_os(DAEMON_ENV).is_some()
+}
+
+/// macOS and Windows keep the clipboard contents after the process exits
+#[cfg(not(target_os = "linux"))]
+fn copy_system(text: &str) -> io::Result<()> {
+    let mut clipboard = arboard::Clipboard::new().map_err(io::Error::other)?;
+    clipboard.set_text(text).map_err(io::Error::other)
+}
+
+/// On X11 and Wayland the clipboard is served by the process that set it, and is gone once that
+/// process exits unless a clipboard manager saves it. A detached copy of this executable, running
+/// [`run_daemon`], takes the text over and serves it until something else is copied. Success is
+/// only reported once the clipboard holds the text while the daemon owns it.
+#[cfg(target_os = "linux")]
+fn copy_system(text: &str) -> io::Result<()> {
+    use std::io::{BufRead, BufReader, Read};
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(env::current_exe()?)
+        .env(DAEMON_ENV, "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Its own process group, so Ctrl-C in the terminal doesn't take the clipboard with it
+        .process_group(0)
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(text.as_bytes())?;
+    drop(stdin);
+
+    let mut ready = String::new();
+    BufReader::new(child.stdout.take().expect("stdout is piped")).read_line(&mut ready)?;
+    if ready.trim_end() != DAEMON_READY {
+        let mut error = String::new();
+        if let Some(mut stderr) = child.stderr.take() {
+            stderr.read_to_string(&mut error)?;
+        }
+        child.wait()?;
+        return Err(io::Error::other(
+            match error.trim() {
+                "" => "the clipboard process exited without taking the clipboard",
+                error => error,
+            }
+            .to_string(),
+        ));
+    }
+
+    let copied = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(io::Error::other)?;
+    if copied != text {
+        return Err(io::Error::other(
+            "the clipboard changed before the copy could be confirmed",
+        ));
+    }
+    Ok(())
+}
+
+/// Printed by the daemon once it owns the clipboard
+#[cfg(target_os = "linux")]
+const DAEMON_READY: &str = "ready";
+
+/// Body of the clipboard daemon: reads the text from stdin, takes the clipboard, reports
+/// [`DAEMON_READY`] on stdout and then serves the text until something else is copied
+#[cfg(target_os = "linux")]
+pub fn run_daemon() -> io::Result<()> {
+    use arboard::SetExtLinux;
+    use std::io::Read;
+
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+
+    let mut clipboard = arboard::Clipboard::new().map_err(io::Error::other)?;
+    clipboard.set_text(&text).map_err(io::Error::other)?;
+    let mut stdout = io::stdout();
+    writeln!(stdout, "{}", DAEMON_READY)?;
+    stdout.flush()?;
+
+    clipboard.set().wait().text(text).map_err(io::Error::other)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn run_daemon() -> io::Result<()> {
+    Err(io::Error::other(
+        "the clipboard daemon is only needed on Linux",
+    ))
+}
+
+fn copy_osc52(text: &str) -> io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut sequence = format!("\x1b]52;c;{}\x07", encoded);
+
+    // tmux only forwards escape sequences to the outer terminal when wrapped in a passthrough
+    if env::var_os("TMUX").is_some() {
+        sequence = format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"));
+    }
+
+    // Write to the terminal directly so the sequence never ends up in redirected output
+    match OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => tty.write_all(sequence.as_bytes()),
+        Err(_) => io::stderr().write_all(sequence.as_bytes()),
+    }
+}
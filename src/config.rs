@@ -0,0 +1,82 @@
+//! Optional per-project configuration.
+
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt, fs, io, path::Path, str::FromStr};
+
+use crate::OutputTemplate;
+
+/// Optional per-project configuration file, read from the directory being processed
+pub const CONFIG_FILE: &str = ".repo_to_text.toml";
+
+/// How AI ignore suggestions are combined with explicitly provided ignores
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AiStrategy {
+    /// Only ask the AI when no --ignore is given
+    Replace,
+    /// Always ask the AI and union its suggestions with CLI and config ignores
+    Merge,
+}
+
+impl fmt::Display for AiStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AiStrategy::Replace => "replace",
+            AiStrategy::Merge => "merge",
+        })
+    }
+}
+
+/// Parses `replace` or `merge`, the names used in the config file
+impl FromStr for AiStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "replace" => Ok(AiStrategy::Replace),
+            "merge" => Ok(AiStrategy::Merge),
+            other => Err(format!(
+                "unknown AI strategy \"{}\" (expected replace or merge)",
+                other
+            )),
+        }
+    }
+}
+
+/// Settings loaded from `.repo_to_text.toml`
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub ignore: Vec<String>,
+    pub ignore_dir: Vec<String>,
+    pub ignore_ext: Vec<String>,
+    pub ignore_file: Vec<String>,
+    pub include: Vec<String>,
+    pub lang: Vec<String>,
+    pub exclude_lang: Vec<String>,
+    pub ai_strategy: Option<AiStrategy>,
+    pub with_docs: bool,
+    pub with_config: bool,
+    pub include_generated: bool,
+    /// Template used when neither `--template` nor `--format` is given
+    pub template: Option<String>,
+    pub templates: BTreeMap<String, OutputTemplate>,
+}
+
+impl ProjectConfig {
+    /// Loads the config file from `dir`, returning an empty config if there is none
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let content = match fs::read_to_string(dir.join(CONFIG_FILE)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+
+        toml::from_str(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", CONFIG_FILE, e),
+            )
+        })
+    }
+}
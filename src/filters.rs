@@ -0,0 +1,1098 @@
+//! Built-in ignore/allow lists and the rules deciding which files are collected.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Component, Path},
+};
+
+use crate::{
+    format_size,
+    generated::{self, GeneratedSign},
+    langs::Language,
+};
+
+pub const DEFAULT_ALLOWED_EXTS: &[&str] = &[
+    "ada",
+    "adb",
+    "ads",
+    "apex",
+    "as",
+    "asm",
+    "astro",
+    "bas",
+    "bat",
+    "c",
+    "cc",
+    "cbl",
+    "cl",
+    "clj",
+    "cljc",
+    "cljs",
+    "cls",
+    "cmake",
+    "coffee",
+    "cr",
+    "cs",
+    "cshtml",
+    "csh",
+    "css",
+    "cue",
+    "cxx",
+    "d",
+    "dart",
+    "edn",
+    "elm",
+    "erl",
+    "ex",
+    "exs",
+    "fs",
+    "fsi",
+    "fsx",
+    "fsscript",
+    "f",
+    "f03",
+    "f08",
+    "f77",
+    "f90",
+    "f95",
+    "gd",
+    "gemspec",
+    "gleam",
+    "glsl",
+    "go",
+    "gradle",
+    "graphql",
+    "gql",
+    "groovy",
+    "gvy",
+    "h",
+    "handlebars",
+    "hbs",
+    "hh",
+    "hpp",
+    "hs",
+    "hx",
+    "hxx",
+    "htm",
+    "html",
+    "hrl",
+    "hcl",
+    "ipynb",
+    "java",
+    "jl",
+    "js",
+    "jsx",
+    "json",
+    "kql",
+    "kt",
+    "kts",
+    "less",
+    "liquid",
+    "lua",
+    "m",
+    "mm",
+    "ml",
+    "mli",
+    "mjs",
+    "move",
+    "nim",
+    "nix",
+    "odin",
+    "php",
+    "phtml",
+    "pl",
+    "pm",
+    "proto",
+    "ps1",
+    "psm1",
+    "pug",
+    "purs",
+    "py",
+    "pyi",
+    "pyx",
+    "q",
+    "r",
+    "rb",
+    "rego",
+    "rs",
+    "s",
+    "sass",
+    "scala",
+    "sbt",
+    "scm",
+    "scss",
+    "sh",
+    "slim",
+    "sol",
+    "sql",
+    "styl",
+    "svelte",
+    "swift",
+    "tcl",
+    "tf",
+    "tfvars",
+    "thrift",
+    "ts",
+    "tsx",
+    "twig",
+    "v",
+    "vb",
+    "vba",
+    "vbs",
+    "vh",
+    "vue",
+    "wgsl",
+    "xml",
+    "zig",
+    "zsh",
+];
+
+/// Documentation added by `--with-docs`
+pub const DOC_EXTS: &[&str] = &["md", "markdown", "mdx", "rst", "adoc"];
+pub const DOC_FILES: &[&str] = &[
+    "README",
+    "LICENSE",
+    "CHANGELOG",
+    "CONTRIBUTING",
+    "AUTHORS",
+    "NOTICE",
+    "COPYING",
+];
+
+/// Configuration added by `--with-config`. `txt` is left out on purpose, as it is mostly notes
+/// and data, so `requirements.txt` and `CMakeLists.txt` are listed by name.
+pub const CONFIG_EXTS: &[&str] = &["toml", "yaml", "yml", "ini", "cfg", "conf", "properties"];
+pub const CONFIG_FILES: &[&str] = &[
+    ".env.example",
+    ".env.sample",
+    ".editorconfig",
+    ".nvmrc",
+    ".tool-versions",
+    "CMakeLists.txt",
+    "Containerfile",
+    "Dockerfile",
+    "Gemfile",
+    "Justfile",
+    "Makefile",
+    "Procfile",
+    "Rakefile",
+    "go.mod",
+    "requirements.txt",
+];
+
+/// Build manifests that describe a project, listed first in the output after the README
+/// when docs or config are included
+pub const MANIFEST_FILES: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "setup.cfg",
+    "requirements.txt",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "build.gradle.kts",
+    "build.sbt",
+    "Gemfile",
+    "composer.json",
+    "mix.exs",
+    "CMakeLists.txt",
+    "Makefile",
+];
+
+pub const DEFAULT_IGNORED_DIRS: &[&str] = &[
+    "__pycache__",
+    "__snapshots__",
+    "_build",
+    "_output",
+    "angular",
+    "bazel-bin",
+    "bazel-out",
+    "bazel-testlogs",
+    "bin",
+    "bower_components",
+    "build",
+    "buck-out",
+    "cache",
+    "cmake-build-debug",
+    "cmake-build-release",
+    "coverage",
+    "dart_tool",
+    "debug",
+    "deriveddata",
+    "dist",
+    "env",
+    "git",
+    "gradle",
+    "hg",
+    "jspm_packages",
+    "logs",
+    "m2",
+    "mypy_cache",
+    "next",
+    "node_modules",
+    "nuxt",
+    "obj",
+    "out",
+    "output",
+    "parcel-cache",
+    "pnpm-store",
+    "pods",
+    "pytest_cache",
+    "release",
+    "reports",
+    "ruff_cache",
+    "serverless",
+    "storybook-static",
+    "svelte-kit",
+    "svn",
+    "target",
+    "temp",
+    "terraform",
+    "tmp",
+    "vendor",
+    "venv",
+    "vercel",
+    "vs",
+    "vscode",
+    "yarn",
+    "yarn_cache",
+];
+
+/// When a default directory rule applies. Names like `bin` or `target` are build output in
+/// some ecosystems and source code in others.
+enum DirContext {
+    /// Only when one of these files sits beside the directory
+    Beside(&'static [&'static str]),
+    /// Only when the directory contains one of these files
+    Containing(&'static [&'static str]),
+    /// Never anywhere under a directory with one of these names, the root included
+    NotIn(&'static [&'static str]),
+}
+
+const CONTEXTUAL_DEFAULTS: &[(&str, DirContext)] = &[
+    (
+        "target",
+        DirContext::Beside(&["Cargo.toml", "pom.xml", "build.sbt"]),
+    ),
+    ("bin", DirContext::NotIn(&["src"])),
+    ("debug", DirContext::NotIn(&["src", "lib", "pkg"])),
+    ("release", DirContext::NotIn(&["src", "lib", "pkg"])),
+    ("env", DirContext::Containing(&["pyvenv.cfg"])),
+];
+
+impl DirContext {
+    /// Describes the context if the directory at `relative` under `root` is in it
+    fn matches(&self, root: &Path, relative: &Path) -> Option<String> {
+        let dir = root.join(relative);
+        match self {
+            DirContext::Beside(files) => {
+                let parent = dir.parent()?;
+                files
+                    .iter()
+                    .find(|file| parent.join(file).is_file())
+                    .map(|file| format!("beside {}", file))
+            }
+            DirContext::Containing(files) => files
+                .iter()
+                .find(|file| dir.join(file).is_file())
+                .map(|file| format!("containing {}", file)),
+            DirContext::NotIn(parents) => {
+                let mut ancestors = relative
+                    .parent()
+                    .into_iter()
+                    .flat_map(Path::components)
+                    .filter_map(|c| match c {
+                        Component::Normal(name) => Some(name),
+                        _ => None,
+                    })
+                    .chain(root.file_name());
+                if ancestors.any(|name| parents.contains(&name.to_string_lossy().as_ref())) {
+                    None
+                } else {
+                    Some(format!("not in {}/", parents.join("/, ")))
+                }
+            }
+        }
+    }
+}
+
+/// A directory rule containing `/`: `/build` only matches at the root, `frontend/dist` matches a
+/// `dist` directly inside any `frontend`
+#[derive(Clone, Debug)]
+struct DirPath {
+    pattern: String,
+    components: Vec<String>,
+    anchored: bool,
+}
+
+impl DirPath {
+    fn parse(pattern: &str) -> Option<Self> {
+        let anchored = pattern.starts_with('/');
+        let components: Vec<String> = pattern
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .map(str::to_string)
+            .collect();
+        if components.is_empty() {
+            return None;
+        }
+        Some(Self {
+            pattern: pattern.to_string(),
+            components,
+            anchored,
+        })
+    }
+
+    fn matches(&self, relative: &[String]) -> bool {
+        if self.anchored {
+            relative == self.components
+        } else {
+            relative.ends_with(&self.components)
+        }
+    }
+}
+
+/// Where an ignore or include rule came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuleSource {
+    Default,
+    Config,
+    Cli,
+    Ai,
+}
+
+impl fmt::Display for RuleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            RuleSource::Default => "default",
+            RuleSource::Config => "from config",
+            RuleSource::Cli => "from CLI",
+            RuleSource::Ai => "from AI",
+        };
+        f.write_str(label)
+    }
+}
+
+/// What an ignore rule matches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IgnoreKind {
+    /// `--ignore`: a directory name and an extension at once, without case or leading dots.
+    /// An item containing `/` is a directory path, as for [`IgnoreKind::Dir`].
+    Any,
+    /// A directory name, matched exactly, or a path such as `/build` or `frontend/dist`
+    Dir,
+    /// A file extension, without case or a leading dot
+    Ext,
+    /// A file name, matched exactly
+    File,
+}
+
+impl fmt::Display for IgnoreKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            IgnoreKind::Any => "directory or extension",
+            IgnoreKind::Dir => "directory",
+            IgnoreKind::Ext => "extension",
+            IgnoreKind::File => "file",
+        };
+        f.write_str(label)
+    }
+}
+
+/// One ignore rule and where it came from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IgnoreRule {
+    pub pattern: String,
+    pub kind: IgnoreKind,
+    pub source: RuleSource,
+}
+
+/// Everything that shapes the filters besides the built-in defaults
+#[derive(Clone, Debug, Default)]
+pub struct FilterSpec {
+    pub ignores: Vec<IgnoreRule>,
+    /// Extensions to include, taking precedence over every ignore
+    pub includes: Vec<(String, RuleSource)>,
+    /// When set, only these languages are allowed instead of [`DEFAULT_ALLOWED_EXTS`]
+    pub languages: Vec<&'static Language>,
+    pub excluded_languages: Vec<&'static Language>,
+    /// Match default and `--ignore` directory names in case too
+    pub case_sensitive_dirs: bool,
+    /// Also allow [`DOC_EXTS`] and [`DOC_FILES`]
+    pub with_docs: bool,
+    /// Also allow [`CONFIG_EXTS`] and [`CONFIG_FILES`]
+    pub with_config: bool,
+    /// Keep lockfiles and other files recognised as generated
+    pub include_generated: bool,
+}
+
+/// Which rule left a path out of the output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// A directory named `dir` on the path matched the ignore rule `rule`
+    IgnoredDir {
+        dir: String,
+        rule: String,
+        source: RuleSource,
+    },
+    /// A macOS AppleDouble metadata file (`._name`)
+    AppleDouble,
+    /// A versioned shared library such as `libfoo.so.1`
+    SharedLibrary,
+    NoExtension,
+    /// The extension was removed from the allowed list by an ignore rule
+    IgnoredExt {
+        ext: String,
+        source: RuleSource,
+    },
+    /// The file name matched an `--ignore-file` rule
+    IgnoredFile {
+        name: String,
+        source: RuleSource,
+    },
+    /// The extension belongs to a language excluded with `--exclude-lang`
+    ExcludedLanguage {
+        ext: String,
+        language: &'static str,
+    },
+    /// The extension isn't in the allowed list
+    ExtNotAllowed {
+        ext: String,
+    },
+    /// The content has NUL bytes near the start
+    Binary,
+    /// A lockfile, minified bundle or other generated file
+    Generated(GeneratedSign),
+    /// Larger than the large file threshold and deselected when prompted
+    TooLarge {
+        size: u64,
+        threshold: u64,
+    },
+    /// Didn't fit in the `--max-tokens` budget
+    TokenBudget,
+    /// Not picked by `--query` or `--rank`
+    NotSelected,
+    /// The file the output is being written to
+    OutputFile,
+    /// Deleted between collecting the files and reading it
+    Deleted,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::IgnoredDir { dir, rule, source } => write!(
+                f,
+                "in ignored directory \"{}\" (rule \"{}\", {})",
+                dir, rule, source
+            ),
+            SkipReason::AppleDouble => f.write_str("AppleDouble metadata file (\"._\" prefix)"),
+            SkipReason::SharedLibrary => {
+                f.write_str("versioned shared library (\".so.\" in the name)")
+            }
+            SkipReason::NoExtension => f.write_str("no file extension"),
+            SkipReason::IgnoredExt { ext, source } => {
+                write!(f, "extension .{} is ignored ({})", ext, source)
+            }
+            SkipReason::IgnoredFile { name, source } => {
+                write!(f, "file name \"{}\" is ignored ({})", name, source)
+            }
+            SkipReason::ExcludedLanguage { ext, language } => {
+                write!(
+                    f,
+                    "extension .{} belongs to excluded language {}",
+                    ext, language
+                )
+            }
+            SkipReason::ExtNotAllowed { ext } => write!(
+                f,
+                "extension .{} is not in the allowed list (add it with --include {})",
+                ext, ext
+            ),
+            SkipReason::Binary => f.write_str("binary content"),
+            SkipReason::Generated(sign) => write!(
+                f,
+                "generated file, {} (keep it with --include-generated)",
+                sign
+            ),
+            SkipReason::TooLarge { size, threshold } => write!(
+                f,
+                "{} is above the {} large file threshold and was deselected",
+                format_size(*size),
+                format_size(*threshold)
+            ),
+            SkipReason::TokenBudget => f.write_str("over the --max-tokens budget"),
+            SkipReason::NotSelected => f.write_str("not selected by --query or --rank"),
+            SkipReason::OutputFile => f.write_str("the output file itself"),
+            SkipReason::Deleted => f.write_str("deleted while the output was being written"),
+        }
+    }
+}
+
+/// The effective ignore and include rules for a run
+#[derive(Clone, Debug)]
+pub struct Filters {
+    /// Defaults and `--ignore` items, without leading dots and lowercased unless case-sensitive
+    ignored_dirs: HashMap<String, RuleSource>,
+    exact_dirs: HashMap<String, RuleSource>,
+    dir_paths: Vec<(DirPath, RuleSource)>,
+    case_sensitive: bool,
+    ignored_exts: HashMap<String, RuleSource>,
+    ignored_files: HashMap<String, RuleSource>,
+    allowed_exts: HashSet<String>,
+    /// Extensionless file names allowed by a language, such as `Dockerfile`
+    allowed_files: HashSet<String>,
+    included_exts: HashMap<String, RuleSource>,
+    languages: Vec<&'static Language>,
+    excluded_languages: Vec<&'static Language>,
+    include_generated: bool,
+}
+
+impl Filters {
+    /// Builds the rules from the defaults and `spec`
+    pub fn new(spec: &FilterSpec) -> Self {
+        let case_sensitive = spec.case_sensitive_dirs;
+        let fold = |name: &str| {
+            let name = name.trim_start_matches('.');
+            if case_sensitive {
+                name.to_string()
+            } else {
+                name.to_lowercase()
+            }
+        };
+
+        let mut ignored_dirs: HashMap<String, RuleSource> = DEFAULT_IGNORED_DIRS
+            .iter()
+            .map(|d| (fold(d), RuleSource::Default))
+            .collect();
+
+        let mut allowed_exts: HashSet<String> = if spec.languages.is_empty() {
+            DEFAULT_ALLOWED_EXTS
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect()
+        } else {
+            spec.languages
+                .iter()
+                .flat_map(|lang| lang.exts.iter().map(|ext| ext.to_string()))
+                .collect()
+        };
+        let mut allowed_files: HashSet<String> = spec
+            .languages
+            .iter()
+            .flat_map(|lang| lang.files.iter().map(|file| file.to_string()))
+            .collect();
+
+        let extras = [
+            (spec.with_docs, DOC_EXTS, DOC_FILES),
+            (spec.with_config, CONFIG_EXTS, CONFIG_FILES),
+        ];
+        for (_, exts, files) in extras.iter().filter(|(enabled, _, _)| *enabled) {
+            allowed_exts.extend(exts.iter().map(|ext| ext.to_string()));
+            allowed_files.extend(files.iter().map(|file| file.to_string()));
+        }
+
+        for lang in &spec.excluded_languages {
+            for ext in lang.exts {
+                allowed_exts.remove(*ext);
+            }
+            for file in lang.files {
+                allowed_files.remove(*file);
+            }
+        }
+
+        let mut exact_dirs = HashMap::new();
+        let mut dir_paths: Vec<(DirPath, RuleSource)> = Vec::new();
+        let mut ignored_exts = HashMap::new();
+        let mut ignored_files = HashMap::new();
+
+        // Union ignores from every source; the first source to name an item is the one reported
+        for rule in &spec.ignores {
+            let cleaned = rule.pattern.trim();
+            if cleaned.is_empty() {
+                continue;
+            }
+
+            match rule.kind {
+                IgnoreKind::Any | IgnoreKind::Dir
+                    if cleaned.trim_end_matches('/').contains('/') =>
+                {
+                    let pattern = cleaned.trim_end_matches('/');
+                    if let Some(path) = DirPath::parse(pattern) {
+                        if !dir_paths.iter().any(|(p, _)| p.pattern == pattern) {
+                            dir_paths.push((path, rule.source));
+                        }
+                    }
+                }
+                // The original heuristic: the item could be either, so it is treated as both
+                IgnoreKind::Any => {
+                    let dir_name = fold(cleaned);
+                    if !dir_name.is_empty() {
+                        allowed_exts.remove(&dir_name.to_lowercase());
+                        // Naming a default explicitly makes it apply everywhere, without the
+                        // context check of contextual defaults
+                        let source = ignored_dirs.entry(dir_name).or_insert(rule.source);
+                        if *source == RuleSource::Default {
+                            *source = rule.source;
+                        }
+                    }
+                }
+                IgnoreKind::Dir => {
+                    let dir_name = cleaned.trim_end_matches('/');
+                    if !dir_name.is_empty() {
+                        exact_dirs
+                            .entry(dir_name.to_string())
+                            .or_insert(rule.source);
+                    }
+                }
+                IgnoreKind::Ext => {
+                    let ext = cleaned.trim_start_matches('.').to_lowercase();
+                    if !ext.is_empty() {
+                        allowed_exts.remove(&ext);
+                        ignored_exts.entry(ext).or_insert(rule.source);
+                    }
+                }
+                IgnoreKind::File => {
+                    ignored_files
+                        .entry(cleaned.to_string())
+                        .or_insert(rule.source);
+                }
+            }
+        }
+
+        // Includes are applied last so they take precedence over every ignore
+        let mut included_exts: HashMap<String, RuleSource> = HashMap::new();
+        for (item, source) in &spec.includes {
+            let cleaned = item.trim();
+            if cleaned.is_empty() {
+                continue;
+            }
+
+            let ext = cleaned.trim_start_matches('.').to_lowercase();
+            if !ext.is_empty() {
+                allowed_exts.insert(ext.clone());
+                included_exts.entry(ext).or_insert(*source);
+            }
+        }
+
+        Self {
+            ignored_dirs,
+            exact_dirs,
+            dir_paths,
+            case_sensitive,
+            ignored_exts,
+            ignored_files,
+            allowed_exts,
+            allowed_files,
+            included_exts,
+            languages: spec.languages.clone(),
+            excluded_languages: spec.excluded_languages.clone(),
+            include_generated: spec.include_generated,
+        }
+    }
+
+    /// Language presets selected in place of the default extension list, if any
+    pub fn languages(&self) -> &[&'static Language] {
+        &self.languages
+    }
+
+    pub fn excluded_languages(&self) -> &[&'static Language] {
+        &self.excluded_languages
+    }
+
+    /// Ignore rules, sorted by pattern
+    pub fn ignore_rules(&self) -> Vec<IgnoreRule> {
+        let kinds = [
+            (&self.ignored_dirs, IgnoreKind::Any),
+            (&self.exact_dirs, IgnoreKind::Dir),
+            (&self.ignored_exts, IgnoreKind::Ext),
+            (&self.ignored_files, IgnoreKind::File),
+        ];
+        let mut rules: Vec<IgnoreRule> = kinds
+            .iter()
+            .flat_map(|(patterns, kind)| {
+                patterns.iter().map(|(pattern, source)| IgnoreRule {
+                    pattern: pattern.clone(),
+                    kind: *kind,
+                    source: *source,
+                })
+            })
+            .chain(self.dir_paths.iter().map(|(path, source)| IgnoreRule {
+                pattern: path.pattern.clone(),
+                kind: IgnoreKind::Dir,
+                source: *source,
+            }))
+            .collect();
+        rules.sort_by(|a, b| a.pattern.cmp(&b.pattern));
+        rules
+    }
+
+    /// Explicit include rules and their sources, sorted by name
+    pub fn include_rules(&self) -> Vec<(&str, RuleSource)> {
+        let mut rules: Vec<(&str, RuleSource)> = self
+            .included_exts
+            .iter()
+            .map(|(ext, source)| (ext.as_str(), *source))
+            .collect();
+        rules.sort_by_key(|(ext, _)| *ext);
+        rules
+    }
+
+    /// Every extension that will be collected, sorted
+    pub fn allowed_exts(&self) -> Vec<&str> {
+        let mut exts: Vec<&str> = self.allowed_exts.iter().map(|e| e.as_str()).collect();
+        exts.sort();
+        exts
+    }
+
+    /// File names collected regardless of their extension, sorted
+    pub fn allowed_files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = self.allowed_files.iter().map(|f| f.as_str()).collect();
+        files.sort();
+        files
+    }
+
+    /// Whether the directory at `relative` under `root` is ignored (its parents aren't checked)
+    pub fn should_ignore_dir(&self, root: &Path, relative: &Path) -> bool {
+        self.dir_skip_reason(root, relative).is_some()
+    }
+
+    pub fn should_ignore_ext(&self, file: &Path) -> bool {
+        self.ext_skip_reason(file).is_some()
+    }
+
+    /// The rule that prunes the directory at `relative` under `root`, if any. Only the
+    /// directory itself is checked, not its parents. `root` should be canonical, as the name
+    /// of the root itself can matter (`bin` is kept when run from inside `src`).
+    pub fn dir_skip_reason(&self, root: &Path, relative: &Path) -> Option<SkipReason> {
+        let name = relative.file_name()?.to_string_lossy();
+        let ignored = |rule: String, source: RuleSource| {
+            Some(SkipReason::IgnoredDir {
+                dir: relative.to_string_lossy().into_owned(),
+                rule,
+                source,
+            })
+        };
+
+        if let Some((rule, source)) = self.exact_dirs.get_key_value(name.as_ref()) {
+            return ignored(rule.clone(), *source);
+        }
+
+        if !self.dir_paths.is_empty() {
+            let components: Vec<String> = relative
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                    _ => None,
+                })
+                .collect();
+            if let Some((path, source)) =
+                self.dir_paths.iter().find(|(p, _)| p.matches(&components))
+            {
+                return ignored(path.pattern.clone(), *source);
+            }
+        }
+
+        let name = name.trim_start_matches('.');
+        let key = if self.case_sensitive {
+            name.to_string()
+        } else {
+            name.to_lowercase()
+        };
+        let (rule, source) = self.ignored_dirs.get_key_value(&key)?;
+
+        // Defaults that are only build output in some ecosystems need their context on disk
+        if *source == RuleSource::Default {
+            if let Some((_, context)) = CONTEXTUAL_DEFAULTS.iter().find(|(d, _)| d == rule) {
+                let context = context.matches(root, relative)?;
+                return ignored(format!("{}, {}", rule, context), *source);
+            }
+        }
+
+        ignored(rule.clone(), *source)
+    }
+
+    /// The rule that skips `file` based on its name alone, if any
+    pub fn file_skip_reason(&self, file: &Path) -> Option<SkipReason> {
+        let Some(filename) = file.file_name() else {
+            return Some(SkipReason::NoExtension);
+        };
+        let filename = filename.to_string_lossy();
+        if filename.starts_with("._") {
+            return Some(SkipReason::AppleDouble);
+        }
+        if let Some(source) = self.ignored_files.get(filename.as_ref()) {
+            return Some(SkipReason::IgnoredFile {
+                name: filename.into_owned(),
+                source: *source,
+            });
+        }
+        if !self.allowed_files.contains(filename.as_ref()) {
+            if let Some(reason) = self.ext_skip_reason(file) {
+                return Some(reason);
+            }
+        }
+        // Checked last, so a lockfile whose extension isn't allowed anyway isn't blamed on this
+        if self.include_generated {
+            return None;
+        }
+        generated::by_name(file).map(SkipReason::Generated)
+    }
+
+    /// Whether generated files are kept
+    pub fn include_generated(&self) -> bool {
+        self.include_generated
+    }
+
+    fn ext_skip_reason(&self, file: &Path) -> Option<SkipReason> {
+        let filename = file
+            .file_name()
+            .map(|f| f.to_string_lossy())
+            .unwrap_or_default();
+
+        // Check if file has no extension
+        if !filename.contains('.') || filename.ends_with('.') {
+            return Some(SkipReason::NoExtension);
+        }
+
+        // Check for .so.* pattern (case-insensitive)
+        if filename.to_lowercase().contains(".so.") {
+            return Some(SkipReason::SharedLibrary);
+        }
+
+        let extension = match file.extension() {
+            Some(ext) => ext.to_string_lossy().to_string().to_lowercase(),
+            None => return Some(SkipReason::NoExtension),
+        };
+
+        if extension.is_empty() {
+            return Some(SkipReason::NoExtension);
+        }
+
+        if self.allowed_exts.contains(&extension) {
+            return None;
+        }
+
+        if let Some(source) = self.ignored_exts.get(&extension) {
+            return Some(SkipReason::IgnoredExt {
+                ext: extension,
+                source: *source,
+            });
+        }
+
+        if let Some(lang) = self
+            .excluded_languages
+            .iter()
+            .find(|lang| lang.exts.contains(&extension.as_str()))
+        {
+            return Some(SkipReason::ExcludedLanguage {
+                ext: extension,
+                language: lang.name,
+            });
+        }
+
+        // `--ignore` items double as extension rules, so an explicit one explains the skip
+        match self.ignored_dirs.get(&extension) {
+            Some(source) if *source != RuleSource::Default => Some(SkipReason::IgnoredExt {
+                ext: extension,
+                source: *source,
+            }),
+            _ => Some(SkipReason::ExtNotAllowed { ext: extension }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, path::PathBuf};
+
+    /// A scratch directory that is removed again when dropped
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("repo_to_text-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn dir(&self, relative: &str) -> &Self {
+            fs::create_dir_all(self.0.join(relative)).unwrap();
+            self
+        }
+
+        fn file(&self, relative: &str) -> &Self {
+            let path = self.0.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+            self
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn components(path: &str) -> Vec<String> {
+        path.split('/').map(str::to_string).collect()
+    }
+
+    fn filters(ignores: &[(&str, IgnoreKind)], case_sensitive_dirs: bool) -> Filters {
+        Filters::new(&FilterSpec {
+            ignores: ignores
+                .iter()
+                .map(|(pattern, kind)| IgnoreRule {
+                    pattern: pattern.to_string(),
+                    kind: *kind,
+                    source: RuleSource::Cli,
+                })
+                .collect(),
+            case_sensitive_dirs,
+            ..FilterSpec::default()
+        })
+    }
+
+    fn ignored_by(filters: &Filters, root: &Path, relative: &str) -> Option<String> {
+        match filters.dir_skip_reason(root, Path::new(relative)) {
+            Some(SkipReason::IgnoredDir { rule, .. }) => Some(rule),
+            other => other.map(|reason| format!("{:?}", reason)),
+        }
+    }
+
+    #[test]
+    fn anchored_dir_path_only_matches_at_the_root() {
+        let path = DirPath::parse("/build").unwrap();
+        assert!(path.matches(&components("build")));
+        assert!(!path.matches(&components("tools/build")));
+    }
+
+    #[test]
+    fn unanchored_dir_path_matches_at_any_depth() {
+        let path = DirPath::parse("frontend/dist").unwrap();
+        assert!(path.matches(&components("frontend/dist")));
+        assert!(path.matches(&components("apps/web/frontend/dist")));
+        assert!(!path.matches(&components("dist")));
+        assert!(!path.matches(&components("frontend/dist/assets")));
+        assert!(!path.matches(&components("backend/dist")));
+    }
+
+    #[test]
+    fn dir_path_ignores_empty_and_dot_components() {
+        let path = DirPath::parse("./frontend//dist/").unwrap();
+        assert_eq!(path.components, components("frontend/dist"));
+        assert!(!path.anchored);
+        assert!(DirPath::parse("/").is_none());
+    }
+
+    #[test]
+    fn dir_rules_with_a_slash_are_paths() {
+        let filters = filters(&[("/scripts", IgnoreKind::Dir)], false);
+        let root = Path::new(".");
+        assert_eq!(
+            ignored_by(&filters, root, "scripts"),
+            Some("/scripts".into())
+        );
+        assert_eq!(ignored_by(&filters, root, "tools/scripts"), None);
+    }
+
+    #[test]
+    fn exact_dir_rules_keep_case_and_dots() {
+        let filters = filters(&[("Fixtures", IgnoreKind::Dir)], false);
+        let root = Path::new(".");
+        assert_eq!(
+            ignored_by(&filters, root, "a/Fixtures"),
+            Some("Fixtures".into())
+        );
+        assert_eq!(ignored_by(&filters, root, "a/fixtures"), None);
+    }
+
+    #[test]
+    fn case_sensitive_dirs_stop_default_folding() {
+        let root = Path::new(".");
+        assert!(ignored_by(&filters(&[], false), root, "Node_Modules").is_some());
+        assert_eq!(ignored_by(&filters(&[], true), root, "Node_Modules"), None);
+        assert!(ignored_by(&filters(&[], true), root, "node_modules").is_some());
+    }
+
+    #[test]
+    fn target_is_only_ignored_beside_a_build_manifest() {
+        let scratch = Scratch::new("target");
+        scratch
+            .file("rust/Cargo.toml")
+            .dir("rust/target")
+            .dir("docs/target");
+        let filters = filters(&[], false);
+
+        assert_eq!(
+            ignored_by(&filters, &scratch.0, "rust/target"),
+            Some("target, beside Cargo.toml".into())
+        );
+        assert_eq!(ignored_by(&filters, &scratch.0, "docs/target"), None);
+    }
+
+    #[test]
+    fn env_is_only_ignored_when_it_is_a_virtualenv() {
+        let scratch = Scratch::new("env");
+        scratch.file("venv1/env/pyvenv.cfg").dir("config/env");
+        let filters = filters(&[], false);
+
+        assert_eq!(
+            ignored_by(&filters, &scratch.0, "venv1/env"),
+            Some("env, containing pyvenv.cfg".into())
+        );
+        assert_eq!(ignored_by(&filters, &scratch.0, "config/env"), None);
+    }
+
+    #[test]
+    fn bin_is_kept_inside_src() {
+        let filters = filters(&[], false);
+        let root = Path::new(".");
+        assert_eq!(ignored_by(&filters, root, "src/bin"), None);
+        assert_eq!(
+            ignored_by(&filters, root, "bin"),
+            Some("bin, not in src/".into())
+        );
+    }
+
+    #[test]
+    fn build_dirs_are_kept_anywhere_under_src() {
+        let filters = filters(&[], false);
+        let root = Path::new("/work/project");
+        assert_eq!(ignored_by(&filters, root, "src/net/debug"), None);
+        assert_eq!(ignored_by(&filters, root, "pkg/cli/release"), None);
+        assert_eq!(ignored_by(&filters, root, "src/tools/bin"), None);
+        assert_eq!(
+            ignored_by(&filters, root, "examples/debug"),
+            Some("debug, not in src/, lib/, pkg/".into())
+        );
+    }
+
+    #[test]
+    fn the_root_counts_as_an_ancestor() {
+        let filters = filters(&[], false);
+        assert_eq!(
+            ignored_by(&filters, Path::new("/work/project/src"), "bin"),
+            None
+        );
+        assert_eq!(
+            ignored_by(&filters, Path::new("/work/project"), "bin"),
+            Some("bin, not in src/".into())
+        );
+    }
+
+    #[test]
+    fn explicit_rules_skip_the_context_check() {
+        let filters = filters(&[("target", IgnoreKind::Any)], false);
+        assert_eq!(
+            ignored_by(&filters, Path::new("."), "docs/target"),
+            Some("target".into())
+        );
+    }
+}
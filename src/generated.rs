@@ -0,0 +1,213 @@
+//! Detecting lockfiles, minified bundles and other generated files, which cost a lot of
+//! context while telling a reader little about the code.
+
+use std::{fmt, path::Path};
+
+/// Lockfiles, matched by exact name
+pub const LOCKFILES: &[&str] = &[
+    "Cargo.lock",
+    "Gemfile.lock",
+    "Pipfile.lock",
+    "Podfile.lock",
+    "bun.lockb",
+    "composer.lock",
+    "flake.lock",
+    "go.sum",
+    "mix.lock",
+    "npm-shrinkwrap.json",
+    "package-lock.json",
+    "packages.lock.json",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "pubspec.lock",
+    "uv.lock",
+    "yarn.lock",
+];
+
+/// File name endings used by minifiers, source maps and code generators
+pub const GENERATED_SUFFIXES: &[&str] = &[
+    ".min.js",
+    ".min.mjs",
+    ".min.css",
+    ".js.map",
+    ".css.map",
+    ".pb.go",
+    ".pb.gw.go",
+    ".pb.cc",
+    ".pb.h",
+    "_pb2.py",
+    "_pb2.pyi",
+    "_pb2_grpc.py",
+    "_generated.rs",
+    ".generated.ts",
+    ".generated.js",
+    ".g.dart",
+    ".freezed.dart",
+    ".designer.cs",
+    ".g.cs",
+];
+
+/// Comments that generators put near the top of their output
+const MARKERS: &[&str] = &[
+    "@generated",
+    "DO NOT EDIT",
+    "Code generated by",
+    "<auto-generated",
+    "This file is automatically generated",
+    "This file was automatically generated",
+];
+
+/// Only the first lines are searched for [`MARKERS`], where generators put them; further
+/// down they are more likely to be in code that talks about generated files
+const MARKER_LINES: usize = 10;
+
+/// Extensions checked for minification. Prose has long lines too, so only formats that
+/// minifiers produce are checked.
+const MINIFIABLE_EXTS: &[&str] = &["js", "mjs", "cjs", "css", "json", "html", "htm", "svg"];
+
+/// Files at least this size with lines at least this long on average count as minified
+const MINIFIED_MIN_BYTES: usize = 2 * 1024;
+const MINIFIED_AVG_LINE: usize = 300;
+
+/// Why a file was considered generated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GeneratedSign {
+    Lockfile,
+    /// The file name ends with one of [`GENERATED_SUFFIXES`]
+    Suffix(&'static str),
+    /// A generator comment near the top of the file
+    Marker(&'static str),
+    Minified {
+        avg_line: usize,
+    },
+}
+
+impl fmt::Display for GeneratedSign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeneratedSign::Lockfile => f.write_str("lockfile"),
+            GeneratedSign::Suffix(suffix) => write!(f, "name ends with \"{}\"", suffix),
+            GeneratedSign::Marker(marker) => write!(f, "\"{}\" marker near the top", marker),
+            GeneratedSign::Minified { avg_line } => {
+                write!(f, "minified, {} characters per line on average", avg_line)
+            }
+        }
+    }
+}
+
+/// Recognises generated files by name alone, so they can be skipped without being read
+pub fn by_name(path: &Path) -> Option<GeneratedSign> {
+    let name = path.file_name()?.to_string_lossy();
+    if LOCKFILES.contains(&name.as_ref()) {
+        return Some(GeneratedSign::Lockfile);
+    }
+    let lower = name.to_lowercase();
+    GENERATED_SUFFIXES
+        .iter()
+        .find(|suffix| lower.ends_with(*suffix))
+        .map(|suffix| GeneratedSign::Suffix(suffix))
+}
+
+/// Recognises generated files by a generator marker near the top or by minification
+pub fn by_content(path: &Path, content: &str) -> Option<GeneratedSign> {
+    let head = content.lines().take(MARKER_LINES).collect::<Vec<_>>();
+    if let Some(marker) = MARKERS
+        .iter()
+        .find(|marker| head.iter().any(|line| line.contains(*marker)))
+    {
+        return Some(GeneratedSign::Marker(marker));
+    }
+
+    let minifiable = path.extension().is_some_and(|ext| {
+        MINIFIABLE_EXTS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+    });
+    if minifiable && content.len() >= MINIFIED_MIN_BYTES {
+        let avg_line = content.len() / content.lines().count().max(1);
+        if avg_line >= MINIFIED_AVG_LINE {
+            return Some(GeneratedSign::Minified { avg_line });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lockfiles_are_matched_by_exact_name() {
+        assert_eq!(
+            by_name(Path::new("web/package-lock.json")),
+            Some(GeneratedSign::Lockfile)
+        );
+        assert_eq!(
+            by_name(Path::new("Cargo.lock")),
+            Some(GeneratedSign::Lockfile)
+        );
+        assert_eq!(by_name(Path::new("package.json")), None);
+        assert_eq!(by_name(Path::new("my-yarn.lock.md")), None);
+    }
+
+    #[test]
+    fn suffixes_are_matched_case_insensitively() {
+        assert_eq!(
+            by_name(Path::new("static/app.min.js")),
+            Some(GeneratedSign::Suffix(".min.js"))
+        );
+        assert_eq!(
+            by_name(Path::new("static/APP.MIN.JS")),
+            Some(GeneratedSign::Suffix(".min.js"))
+        );
+        assert_eq!(
+            by_name(Path::new("Form1.Designer.cs")),
+            Some(GeneratedSign::Suffix(".designer.cs"))
+        );
+        assert_eq!(by_name(Path::new("src/admin.js")), None);
+        assert_eq!(by_name(Path::new("src/minimal.js")), None);
+    }
+
+    #[test]
+    fn markers_only_count_near_the_top() {
+        let path = Path::new("api.go");
+        let at = |line: usize| {
+            let mut lines = vec!["package api"; MARKER_LINES + 5];
+            lines[line] = "// Code generated by protoc-gen-go. DO NOT EDIT.";
+            lines.join("\n")
+        };
+
+        assert_eq!(
+            by_content(path, &at(0)),
+            Some(GeneratedSign::Marker("DO NOT EDIT"))
+        );
+        assert_eq!(
+            by_content(path, &at(MARKER_LINES - 1)),
+            Some(GeneratedSign::Marker("DO NOT EDIT"))
+        );
+        assert_eq!(by_content(path, &at(MARKER_LINES)), None);
+    }
+
+    #[test]
+    fn long_lines_only_count_as_minified_in_minifiable_formats() {
+        let line = "a".repeat(MINIFIED_AVG_LINE);
+        let long_lines = [line.as_str(); 10].join("\n");
+        assert!(long_lines.len() >= MINIFIED_MIN_BYTES);
+
+        assert!(matches!(
+            by_content(Path::new("dist/bundle.js"), &long_lines),
+            Some(GeneratedSign::Minified { avg_line }) if avg_line >= MINIFIED_AVG_LINE
+        ));
+        assert_eq!(by_content(Path::new("docs/essay.md"), &long_lines), None);
+        assert_eq!(by_content(Path::new("notes.txt"), &long_lines), None);
+    }
+
+    #[test]
+    fn small_or_short_lined_files_are_not_minified() {
+        let small = "x".repeat(MINIFIED_MIN_BYTES - 1);
+        assert_eq!(by_content(Path::new("tiny.js"), &small), None);
+
+        let line = "x".repeat(MINIFIED_AVG_LINE / 2);
+        let readable = [line.as_str(); 100].join("\n");
+        assert!(readable.len() >= MINIFIED_MIN_BYTES);
+        assert_eq!(by_content(Path::new("app.js"), &readable), None);
+    }
+}
@@ -0,0 +1,375 @@
+//! Per-file metadata lines, filled in from a `--file-header` template.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{cache::content_hash, format_size, langs};
+
+/// Placeholders a template can use, and what they are replaced with
+pub const PLACEHOLDERS: &[(&str, &str)] = &[
+    ("path", "path relative to the root"),
+    ("language", "language preset, or the extension"),
+    ("size", "size on disk"),
+    ("lines", "line count"),
+    ("tokens", "estimated tokens of the content as output"),
+    ("modified", "last modified time, in UTC"),
+    ("author", "author of the last commit touching the file"),
+    ("commit", "short hash of the last commit touching the file"),
+    ("commit_date", "date of the last commit touching the file"),
+    ("hash", "MD5 hash of the content"),
+];
+
+/// Placeholders that need `git log`
+const GIT_PLACEHOLDERS: &[&str] = &["author", "commit", "commit_date"];
+
+/// A parsed `--file-header` template such as `{path} ({size}, last changed by {author})`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileHeader {
+    template: String,
+}
+
+/// Everything known about a file when its header is written
+pub(crate) struct FileFacts<'a> {
+    pub root: &'a Path,
+    pub path: &'a Path,
+    /// `path` as written in the output
+    pub output_path: &'a str,
+    pub content: &'a [u8],
+    pub modified: Option<SystemTime>,
+    pub tokens: u64,
+    pub commits: &'a LastCommits,
+}
+
+impl FileHeader {
+    /// Checks that every `{placeholder}` in `template` is one of [`PLACEHOLDERS`]
+    pub fn parse(template: &str) -> io::Result<Self> {
+        for name in placeholders(template) {
+            if !PLACEHOLDERS.iter().any(|(known, _)| *known == name) {
+                let known: Vec<&str> = PLACEHOLDERS.iter().map(|(name, _)| *name).collect();
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Unknown placeholder {{{}}} in --file-header (expected one of {})",
+                        name,
+                        known.join(", ")
+                    ),
+                ));
+            }
+        }
+        Ok(Self {
+            template: template.to_string(),
+        })
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Whether filling in the template runs `git log`
+    pub fn uses_git(&self) -> bool {
+        placeholders(&self.template).any(|name| GIT_PLACEHOLDERS.contains(&name))
+    }
+
+    /// Whether the header shows the modification time, so a touched file's block changes
+    /// even when its content doesn't
+    pub fn uses_modified(&self) -> bool {
+        placeholders(&self.template).any(|name| name == "modified")
+    }
+
+    pub(crate) fn render(&self, facts: &FileFacts) -> String {
+        let relative = facts.path.strip_prefix(facts.root).unwrap_or(facts.path);
+        let commit = self
+            .uses_git()
+            .then(|| facts.commits.get(relative))
+            .flatten();
+
+        let mut out = String::with_capacity(self.template.len() * 2);
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            out.push_str(&rest[..start]);
+            let name = &rest[start + 1..start + len];
+            let value = match name {
+                "path" => facts.output_path.to_string(),
+                "language" => language(facts.path),
+                "size" => format_size(facts.content.len() as u64),
+                "lines" => String::from_utf8_lossy(facts.content)
+                    .lines()
+                    .count()
+                    .to_string(),
+                "tokens" => facts.tokens.to_string(),
+                "modified" => facts.modified.map(utc_timestamp).unwrap_or_default(),
+                "author" => commit
+                    .as_ref()
+                    .map(|c| c.author.clone())
+                    .unwrap_or_default(),
+                "commit" => commit.as_ref().map(|c| c.hash.clone()).unwrap_or_default(),
+                "commit_date" => commit.as_ref().map(|c| c.date.clone()).unwrap_or_default(),
+                "hash" => content_hash(facts.content),
+                _ => rest[start..=start + len].to_string(),
+            };
+            out.push_str(&value);
+            rest = &rest[start + len + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// Names inside `{...}` in `template`
+fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+}
+
+/// The language preset `path` belongs to, or its extension
+pub(crate) fn language(path: &Path) -> String {
+    match langs::language_of(path) {
+        Some(lang) => lang.name.to_string(),
+        None => path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default(),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Commit {
+    hash: String,
+    author: String,
+    date: String,
+}
+
+/// The last commit touching each of a run's files. Filled in on first use by a single
+/// `git log` pass over the history, which stops once every tracked file has been seen, rather
+/// than one `git log` per file.
+pub(crate) struct LastCommits {
+    root: PathBuf,
+    files: Vec<PathBuf>,
+    commits: OnceLock<HashMap<Vec<u8>, Commit>>,
+}
+
+impl LastCommits {
+    /// `files` are paths relative to `root`
+    pub fn new(root: &Path, files: Vec<PathBuf>) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            files,
+            commits: OnceLock::new(),
+        }
+    }
+
+    /// The last commit touching `relative`, or `None` outside a git repository or for a file
+    /// that was never committed
+    fn get(&self, relative: &Path) -> Option<&Commit> {
+        self.commits
+            .get_or_init(|| {
+                let tracked = tracked_files(&self.root);
+                let wanted = self
+                    .files
+                    .iter()
+                    .map(|path| git_key(path))
+                    .filter(|key| tracked.contains(key))
+                    .collect();
+                read_log(&self.root, wanted).unwrap_or_default()
+            })
+            .get(&git_key(relative))
+    }
+}
+
+/// `relative` as git writes it: raw bytes with `/` separators
+fn git_key(relative: &Path) -> Vec<u8> {
+    let mut key = relative.as_os_str().as_encoded_bytes().to_vec();
+    if cfg!(windows) {
+        for byte in &mut key {
+            if *byte == b'\\' {
+                *byte = b'/';
+            }
+        }
+    }
+    key
+}
+
+/// Files under `root` that git tracks, relative to `root`
+fn tracked_files(root: &Path) -> HashSet<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["ls-files", "-z"])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => output
+            .stdout
+            .split(|byte| *byte == 0)
+            .filter(|name| !name.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect(),
+        _ => HashSet::new(),
+    }
+}
+
+/// Separates commits in the `git log` output
+const RECORD_START: u8 = 0x1e;
+
+/// Walks the history from HEAD, newest first, recording the first commit seen for each of
+/// `wanted` and stopping as soon as all of them have one
+fn read_log(root: &Path, mut wanted: HashSet<Vec<u8>>) -> io::Result<HashMap<Vec<u8>, Commit>> {
+    let mut commits = HashMap::new();
+    if wanted.is_empty() {
+        return Ok(commits);
+    }
+
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args([
+            "log",
+            "--relative",
+            "--name-only",
+            "-z",
+            "--format=%x1e%h%x00%an%x00%cs%x00",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let mut reader = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let mut record = Vec::new();
+    while !wanted.is_empty() {
+        record.clear();
+        if reader.read_until(RECORD_START, &mut record)? == 0 {
+            break;
+        }
+        if record.last() == Some(&RECORD_START) {
+            record.pop();
+        }
+
+        // `hash\0author\0date\0\nname\0name\0...`
+        let mut fields = record.split(|byte| *byte == 0);
+        let (Some(hash), Some(author), Some(date)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        for name in fields {
+            let name = name.strip_prefix(b"\n").unwrap_or(name);
+            if wanted.remove(name) {
+                commits.insert(
+                    name.to_vec(),
+                    Commit {
+                        hash: String::from_utf8_lossy(hash).into_owned(),
+                        author: String::from_utf8_lossy(author).into_owned(),
+                        date: String::from_utf8_lossy(date).into_owned(),
+                    },
+                );
+            }
+        }
+    }
+
+    // The rest of the history isn't needed
+    let _ = child.kill();
+    let _ = child.wait();
+    Ok(commits)
+}
+
+/// The current commit of the repository at `root`, if it is one
+pub(crate) fn head_commit(root: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Formats `time` as `YYYY-MM-DD HH:MM:SS UTC`
+fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let (days, secs_of_day) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Howard Hinnant's days-to-civil conversion
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn formats_utc_timestamps() {
+        assert_eq!(utc_timestamp(at(0)), "1970-01-01 00:00:00 UTC");
+        assert_eq!(utc_timestamp(at(951_782_400)), "2000-02-29 00:00:00 UTC");
+        assert_eq!(utc_timestamp(at(1_709_251_199)), "2024-02-29 23:59:59 UTC");
+        assert_eq!(utc_timestamp(at(4_102_444_800)), "2100-01-01 00:00:00 UTC");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        let error = FileHeader::parse("{path} by {owner}").unwrap_err();
+        assert!(error.to_string().contains("{owner}"));
+        assert!(FileHeader::parse("{path}, {size}").is_ok());
+    }
+
+    #[test]
+    fn knows_which_placeholders_need_git_or_mtime() {
+        let header = FileHeader::parse("{path} ({lines} lines)").unwrap();
+        assert!(!header.uses_git() && !header.uses_modified());
+        assert!(FileHeader::parse("{commit_date}").unwrap().uses_git());
+        assert!(FileHeader::parse("{modified}").unwrap().uses_modified());
+    }
+
+    #[test]
+    fn fills_in_placeholders() {
+        let header = FileHeader::parse("{path}: {size}, {lines} lines, ~{tokens} tokens").unwrap();
+        let commits = LastCommits::new(Path::new("."), Vec::new());
+        let rendered = header.render(&FileFacts {
+            root: Path::new("."),
+            path: Path::new("./src/main.rs"),
+            output_path: "src/main.rs",
+            content: b"fn main() {\n}\n",
+            modified: None,
+            tokens: 4,
+            commits: &commits,
+        });
+        assert_eq!(rendered, "src/main.rs: 14B, 2 lines, ~4 tokens");
+    }
+}
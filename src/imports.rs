@@ -0,0 +1,166 @@
+//! Best-effort detection of the local files a source file imports directly.
+//!
+//! Only relative/project-local imports are considered, and resolution is purely lexical:
+//! the returned candidates may not exist and are matched against the collected files.
+
+use regex::Regex;
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+const JS_EXTS: &[&str] = &["ts", "tsx", "js", "jsx", "mjs", "cjs", "vue", "svelte"];
+const C_EXTS: &[&str] = &["c", "cc", "cpp", "cxx", "h", "hh", "hpp", "hxx", "m", "mm"];
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+/// Lexically resolves `.` and `..` components so paths from different sources compare equal
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Returns the paths `file` may import, relative to the same root as `file`
+pub fn import_candidates(file: &Path, content: &str) -> Vec<PathBuf> {
+    let ext = file
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let dir = file.parent().unwrap_or(Path::new(""));
+
+    match ext.as_str() {
+        "rs" => rust_candidates(file, dir, content),
+        "py" | "pyi" => python_candidates(dir, content),
+        e if JS_EXTS.contains(&e) => js_candidates(dir, content),
+        e if C_EXTS.contains(&e) => c_candidates(dir, content),
+        _ => vec![],
+    }
+}
+
+fn rust_candidates(file: &Path, dir: &Path, content: &str) -> Vec<PathBuf> {
+    static MOD_RE: OnceLock<Regex> = OnceLock::new();
+    static USE_RE: OnceLock<Regex> = OnceLock::new();
+    let mod_re = regex(&MOD_RE, r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*;");
+    let use_re = regex(
+        &USE_RE,
+        r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+crate::([\w:]+)",
+    );
+
+    let mut candidates = Vec::new();
+
+    // `mod foo;` lives next to main.rs/lib.rs/mod.rs, or in a directory named after any other file
+    let stem = file.file_stem().map(|s| s.to_string_lossy().to_string());
+    let mod_dir = match stem.as_deref() {
+        Some("main") | Some("lib") | Some("mod") | None => dir.to_path_buf(),
+        Some(stem) => dir.join(stem),
+    };
+    for caps in mod_re.captures_iter(content) {
+        candidates.push(mod_dir.join(format!("{}.rs", &caps[1])));
+        candidates.push(mod_dir.join(&caps[1]).join("mod.rs"));
+    }
+
+    // `use crate::a::b` may point at src/a.rs or src/a/b.rs
+    let src_root = file
+        .ancestors()
+        .find(|a| a.file_name().is_some_and(|n| n == "src"));
+    if let Some(src_root) = src_root {
+        for caps in use_re.captures_iter(content) {
+            let mut module = src_root.to_path_buf();
+            for segment in caps[1].split("::").filter(|s| !s.is_empty()) {
+                module.push(segment);
+                candidates.push(module.with_extension("rs"));
+                candidates.push(module.join("mod.rs"));
+            }
+        }
+    }
+
+    candidates
+}
+
+fn python_candidates(dir: &Path, content: &str) -> Vec<PathBuf> {
+    static FROM_RE: OnceLock<Regex> = OnceLock::new();
+    static IMPORT_RE: OnceLock<Regex> = OnceLock::new();
+    let from_re = regex(
+        &FROM_RE,
+        r"(?m)^\s*from\s+(\.*)([\w.]*)\s+import\s+([\w, ]+)",
+    );
+    let import_re = regex(&IMPORT_RE, r"(?m)^\s*import\s+([\w.]+)");
+
+    let mut candidates = Vec::new();
+    let mut push_module = |base: PathBuf| {
+        candidates.push(base.with_extension("py"));
+        candidates.push(base.join("__init__.py"));
+    };
+
+    for caps in from_re.captures_iter(content) {
+        // Relative imports start from the file's package; absolute ones from the root
+        let mut base = match caps[1].len() {
+            0 => PathBuf::new(),
+            dots => {
+                let mut base = dir.to_path_buf();
+                for _ in 1..dots {
+                    base.push("..");
+                }
+                base
+            }
+        };
+        for part in caps[2].split('.').filter(|p| !p.is_empty()) {
+            base.push(part);
+        }
+        if !caps[2].is_empty() {
+            push_module(base.clone());
+        }
+        // `from . import x` and `from pkg import submodule` name modules too
+        for name in caps[3].split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            push_module(base.join(name));
+        }
+    }
+
+    for caps in import_re.captures_iter(content) {
+        push_module(caps[1].split('.').collect());
+    }
+
+    candidates
+}
+
+fn js_candidates(dir: &Path, content: &str) -> Vec<PathBuf> {
+    static IMPORT_RE: OnceLock<Regex> = OnceLock::new();
+    let import_re = regex(
+        &IMPORT_RE,
+        r#"(?:\bfrom|\bimport|\brequire\s*\()\s*\(?\s*['"](\.{1,2}/[^'"]+)['"]"#,
+    );
+
+    let mut candidates = Vec::new();
+    for caps in import_re.captures_iter(content) {
+        let base = dir.join(&caps[1]);
+        candidates.push(base.clone());
+        for ext in JS_EXTS {
+            let mut with_ext = base.clone().into_os_string();
+            with_ext.push(format!(".{}", ext));
+            candidates.push(with_ext.into());
+            candidates.push(base.join(format!("index.{}", ext)));
+        }
+    }
+    candidates
+}
+
+fn c_candidates(dir: &Path, content: &str) -> Vec<PathBuf> {
+    static INCLUDE_RE: OnceLock<Regex> = OnceLock::new();
+    let include_re = regex(&INCLUDE_RE, r#"(?m)^\s*#\s*include\s*"([^"]+)""#);
+
+    include_re
+        .captures_iter(content)
+        .map(|caps| dir.join(&caps[1]))
+        .collect()
+}
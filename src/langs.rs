@@ -0,0 +1,168 @@
+//! Named language presets, so whole ecosystems can be selected without listing extensions.
+
+use std::{cmp::Reverse, collections::HashMap, fs, io, path::Path};
+use walkdir::WalkDir;
+
+use crate::filters::{FilterSpec, Filters};
+
+/// A named group of extensions and extensionless file names
+#[derive(Debug, PartialEq, Eq)]
+pub struct Language {
+    pub name: &'static str,
+    pub exts: &'static [&'static str],
+    /// Exact file names, for files like `Dockerfile` that have no useful extension
+    pub files: &'static [&'static str],
+}
+
+pub const LANGUAGES: &[Language] = &[
+    Language {
+        name: "rust",
+        exts: &["rs"],
+        files: &[],
+    },
+    Language {
+        name: "python",
+        exts: &["py", "pyi", "pyx", "ipynb"],
+        files: &[],
+    },
+    Language {
+        name: "web",
+        exts: &[
+            "js", "jsx", "mjs", "cjs", "ts", "tsx", "mts", "cts", "html", "htm", "css", "scss",
+            "sass", "less", "styl", "vue", "svelte", "astro",
+        ],
+        files: &[],
+    },
+    Language {
+        name: "jvm",
+        exts: &[
+            "java", "kt", "kts", "scala", "sbt", "groovy", "gvy", "gradle", "clj", "cljs", "cljc",
+            "edn",
+        ],
+        files: &[],
+    },
+    Language {
+        name: "go",
+        exts: &["go"],
+        files: &[],
+    },
+    Language {
+        name: "c",
+        exts: &["c", "h", "cc", "cpp", "cxx", "hh", "hpp", "hxx", "m", "mm"],
+        files: &[],
+    },
+    Language {
+        name: "dotnet",
+        exts: &["cs", "cshtml", "fs", "fsi", "fsx", "fsscript", "vb"],
+        files: &[],
+    },
+    Language {
+        name: "ruby",
+        exts: &["rb", "gemspec"],
+        files: &[],
+    },
+    Language {
+        name: "php",
+        exts: &["php", "phtml"],
+        files: &[],
+    },
+    Language {
+        name: "shell",
+        exts: &["sh", "bash", "zsh", "csh", "ps1", "psm1"],
+        files: &[],
+    },
+    Language {
+        name: "infra",
+        exts: &["tf", "tfvars", "hcl", "yaml", "yml", "nix", "dockerfile"],
+        files: &["Dockerfile", "Containerfile"],
+    },
+    Language {
+        name: "docs",
+        exts: &["md", "markdown", "rst", "adoc"],
+        files: &[],
+    },
+];
+
+/// Selects the languages detected with [`detect`] instead of naming them
+pub const AUTO: &str = "auto";
+
+/// A language needs at least this share of the recognised source bytes to count as dominant
+const DOMINANT_SHARE: f64 = 0.1;
+
+pub fn find(name: &str) -> Option<&'static Language> {
+    LANGUAGES
+        .iter()
+        .find(|lang| lang.name.eq_ignore_ascii_case(name))
+}
+
+/// Resolves preset names, expanding [`AUTO`] to the languages detected under `root`
+pub fn resolve(names: &[String], root: &Path) -> io::Result<Vec<&'static Language>> {
+    let mut langs: Vec<&'static Language> = Vec::new();
+    for name in names {
+        let name = name.trim();
+        let found = if name.eq_ignore_ascii_case(AUTO) {
+            detect(root)
+        } else {
+            let lang = find(name).ok_or_else(|| {
+                let known: Vec<&str> = LANGUAGES.iter().map(|lang| lang.name).collect();
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Unknown language \"{}\" (expected {} or {})",
+                        name,
+                        known.join(", "),
+                        AUTO
+                    ),
+                )
+            })?;
+            vec![lang]
+        };
+        for lang in found {
+            if !langs.contains(&lang) {
+                langs.push(lang);
+            }
+        }
+    }
+    Ok(langs)
+}
+
+/// Finds the languages making up at least a tenth of the recognised source bytes under `root`,
+/// largest first. Directories the default rules ignore aren't counted.
+pub fn detect(root: &Path) -> Vec<&'static Language> {
+    let defaults = Filters::new(&FilterSpec::default());
+    let canonical_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let mut bytes: HashMap<&'static str, u64> = HashMap::new();
+
+    let walker = WalkDir::new(root).into_iter().filter_entry(|entry| {
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        !entry.file_type().is_dir() || !defaults.should_ignore_dir(&canonical_root, relative)
+    });
+    for entry in walker.filter_map(Result::ok) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Some(lang) = language_of(entry.path()) else {
+            continue;
+        };
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        *bytes.entry(lang.name).or_default() += size;
+    }
+
+    let total: u64 = bytes.values().sum();
+    let mut dominant: Vec<(&'static Language, u64)> = LANGUAGES
+        .iter()
+        .filter_map(|lang| bytes.get(lang.name).map(|b| (lang, *b)))
+        .filter(|(_, b)| total > 0 && *b as f64 / total as f64 >= DOMINANT_SHARE)
+        .collect();
+    dominant.sort_by_key(|(_, bytes)| Reverse(*bytes));
+    dominant.into_iter().map(|(lang, _)| lang).collect()
+}
+
+pub(crate) fn language_of(path: &Path) -> Option<&'static Language> {
+    let name = path.file_name()?.to_string_lossy();
+    if let Some(lang) = LANGUAGES.iter().find(|l| l.files.contains(&name.as_ref())) {
+        return Some(lang);
+    }
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    LANGUAGES.iter().find(|l| l.exts.contains(&ext.as_str()))
+}
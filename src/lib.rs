@@ -0,0 +1,1238 @@
+//! Converts a code repository into a single text document for feeding to LLMs.
+//!
+//! ```no_run
+//! use repo_to_text::{OutputFormat, RepoToText};
+//!
+//! let repo = RepoToText::builder()
+//!     .root("path/to/repo")
+//!     .include(["md", "toml"])
+//!     .format(OutputFormat::Markdown)
+//!     .build()?;
+//!
+//! for entry in repo.collect()? {
+//!     println!("{} (~{} tokens)", entry.path.display(), entry.estimated_tokens);
+//! }
+//! repo.render(std::io::stdout().lock())?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+pub mod ai;
+mod bm25;
+mod cache;
+pub mod config;
+pub mod filters;
+pub mod generated;
+mod header;
+mod imports;
+pub mod langs;
+mod notebook;
+mod paths;
+mod render;
+mod summarize;
+mod template;
+mod truncate;
+
+pub use cache::default_cache_dir;
+pub use filters::{FilterSpec, Filters, IgnoreKind, IgnoreRule, RuleSource, SkipReason};
+pub use header::FileHeader;
+pub use paths::{absolute, display_path, repo_name};
+pub use render::{LineNumbers, OutputFormat};
+pub use template::{OutputTemplate, BUILTIN_TEMPLATES};
+pub use truncate::Truncation;
+
+use rayon::iter::ParallelBridge;
+use rayon::prelude::*;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+use walkdir::WalkDir;
+
+use cache::{Cache, RenderedBlock};
+use generated::GeneratedSign;
+use header::{FileFacts, LastCommits};
+use template::{DocumentContext, FileContext, FileSummary, Templates};
+
+/// Upper bound on the source bytes held in memory at once while rendering
+const RENDER_BATCH_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Files above this size are reported as large, so callers can confirm them
+pub const DEFAULT_LARGE_FILE_THRESHOLD: u64 = 1024 * 1024; // 1MB in bytes
+
+/// JSON, CSV, TSV, SQL and XML files at least this large are summarised instead of included
+pub const DEFAULT_DATA_SUMMARY_THRESHOLD: u64 = 64 * 1024;
+
+/// A file selected for output, with the metadata gathered while collecting it
+#[derive(Clone, Debug)]
+pub struct FileEntry {
+    /// Path as walked from the root
+    pub path: PathBuf,
+    pub size: u64,
+    /// Estimated tokens once rendered, capped for files that will be truncated or summarised
+    pub estimated_tokens: u64,
+    pub modified: Option<SystemTime>,
+    /// BM25 score, when files were selected with [`RepoToTextBuilder::rank`]
+    pub score: Option<f64>,
+    /// Selected by [`RepoToTextBuilder::query`] only because a relevant file imports it
+    pub via_import: bool,
+}
+
+/// Totals for one rendered document
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    pub files: usize,
+    /// Bytes of rendered file blocks plus the prefix and suffix, excluding the document header
+    pub bytes: u64,
+    pub estimated_tokens: u64,
+    pub cache_hits: usize,
+    pub cache_misses: usize,
+    /// Files left out once their content turned out to be binary or generated, or that were
+    /// deleted before they could be read
+    pub skipped: Vec<Skipped>,
+    /// Files whose paths aren't valid UTF-8, so they were written with the invalid bytes escaped
+    pub escaped_paths: Vec<PathBuf>,
+}
+
+/// A file or directory left out of the output, and the rule that decided it.
+/// Pruned directories are reported once, without their contents.
+#[derive(Clone, Debug)]
+pub struct Skipped {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+impl FileEntry {
+    fn new(path: PathBuf) -> Self {
+        let metadata = path.metadata().ok();
+        let size = metadata.as_ref().map(|m| m.len()).unwrap_or(0);
+
+        Self {
+            path,
+            size,
+            estimated_tokens: estimate_tokens(size),
+            modified: metadata.and_then(|m| m.modified().ok()),
+            score: None,
+            via_import: false,
+        }
+    }
+}
+
+/// Configures a [`RepoToText`]. Created with [`RepoToText::builder`].
+pub struct RepoToTextBuilder {
+    root: PathBuf,
+    ignores: Vec<IgnoreRule>,
+    includes: Vec<(String, RuleSource)>,
+    format: OutputFormat,
+    query: Option<String>,
+    rank: Option<(String, usize)>,
+    max_tokens: Option<u64>,
+    large_file_threshold: u64,
+    cache_dir: Option<PathBuf>,
+    /// Fail to build when the cache can't be opened, rather than leaving it out
+    cache_required: bool,
+    case_sensitive_dirs: bool,
+    langs: Vec<String>,
+    exclude_langs: Vec<String>,
+    with_docs: bool,
+    with_config: bool,
+    notebook_outputs: bool,
+    include_generated: bool,
+    data_summary_threshold: Option<u64>,
+    max_file_size: Option<u64>,
+    truncation: Truncation,
+    line_numbers: Option<LineNumbers>,
+    file_header: Option<FileHeader>,
+    template: Option<OutputTemplate>,
+    path_prefix: Option<String>,
+    output_file: Option<PathBuf>,
+    prefix: String,
+    suffix: String,
+}
+
+impl RepoToTextBuilder {
+    /// Directory to walk. Defaults to the current directory.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Extra directories or extensions to ignore, reported as coming from the CLI
+    pub fn ignore<I, S>(self, items: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignore_from(items, RuleSource::Cli)
+    }
+
+    /// Extra directories or extensions to ignore, tagged with where they came from
+    pub fn ignore_from<I, S>(self, items: I, source: RuleSource) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignore_kind_from(items, IgnoreKind::Any, source)
+    }
+
+    /// Directory names to ignore, matched exactly
+    pub fn ignore_dir<I, S>(self, items: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignore_kind_from(items, IgnoreKind::Dir, RuleSource::Cli)
+    }
+
+    /// Extensions to ignore, without touching directories of the same name
+    pub fn ignore_ext<I, S>(self, items: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignore_kind_from(items, IgnoreKind::Ext, RuleSource::Cli)
+    }
+
+    /// File names to ignore, matched exactly
+    pub fn ignore_file<I, S>(self, items: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignore_kind_from(items, IgnoreKind::File, RuleSource::Cli)
+    }
+
+    /// Ignore rules of one kind, tagged with where they came from
+    pub fn ignore_kind_from<I, S>(mut self, items: I, kind: IgnoreKind, source: RuleSource) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignores
+            .extend(items.into_iter().map(|item| IgnoreRule {
+                pattern: item.into(),
+                kind,
+                source,
+            }));
+        self
+    }
+
+    /// Extensions to include even if a default or ignore rule would skip them
+    pub fn include<I, S>(self, items: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.include_from(items, RuleSource::Cli)
+    }
+
+    /// Extensions to include, tagged with where they came from
+    pub fn include_from<I, S>(mut self, items: I, source: RuleSource) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.includes
+            .extend(items.into_iter().map(|item| (item.into(), source)));
+        self
+    }
+
+    /// Use the built-in template for `format`. Ignored when a [`template`](Self::template)
+    /// is set.
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Render the document with a custom template instead of a built-in format
+    pub fn template(mut self, template: OutputTemplate) -> Self {
+        self.template = Some(template);
+        self
+    }
+
+    /// Text written before the document header, such as instructions for the model reading it
+    pub fn prefix(mut self, text: impl Into<String>) -> Self {
+        self.prefix = text.into();
+        self
+    }
+
+    /// Text written after the last file, such as the question to answer about the code
+    pub fn suffix(mut self, text: impl Into<String>) -> Self {
+        self.suffix = text.into();
+        self
+    }
+
+    /// Summarise JSON, CSV, TSV, SQL and XML files of at least this many bytes: the JSON
+    /// structure, the CSV header and first rows, the SQL schema, or XML element counts.
+    /// `None` always includes them in full.
+    pub fn data_summary_threshold(mut self, bytes: Option<u64>) -> Self {
+        self.data_summary_threshold = bytes;
+        self
+    }
+
+    /// Prefix every line with its number in the file on disk. Truncated files keep their
+    /// original numbers; notebooks and summarised data files, which don't match lines on disk,
+    /// aren't numbered.
+    pub fn line_numbers(mut self, line_numbers: Option<LineNumbers>) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    /// Write a metadata line under each file's heading, such as the size, last commit or
+    /// content hash
+    pub fn file_header(mut self, file_header: Option<FileHeader>) -> Self {
+        self.file_header = file_header;
+        self
+    }
+
+    /// Put this directory in front of every path in the output, such as the name from
+    /// [`repo_name`]. Paths are otherwise relative to the root.
+    pub fn path_prefix(mut self, prefix: Option<String>) -> Self {
+        self.path_prefix = prefix
+            .map(|prefix| prefix.replace('\\', "/").trim_matches('/').to_string())
+            .filter(|prefix| !prefix.is_empty());
+        self
+    }
+
+    /// The file the document is written to, canonicalised with [`absolute`]. It is never
+    /// collected, so the output of an earlier run inside the root isn't fed into the next.
+    pub fn output_file(mut self, path: Option<PathBuf>) -> Self {
+        self.output_file = path;
+        self
+    }
+
+    /// Cut files larger than this many bytes down with the [`truncation`](Self::truncation)
+    /// strategy, instead of including them in full
+    pub fn max_file_size(mut self, bytes: Option<u64>) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// How files over [`max_file_size`](Self::max_file_size) are cut down. Defaults to the
+    /// first 200 lines.
+    pub fn truncation(mut self, truncation: Truncation) -> Self {
+        self.truncation = truncation;
+        self
+    }
+
+    /// Keep lockfiles, minified bundles and files with generator markers, which are skipped
+    /// by default
+    pub fn include_generated(mut self, include_generated: bool) -> Self {
+        self.include_generated = include_generated;
+        self
+    }
+
+    /// Keep the text outputs of notebook code cells. Image and other binary outputs are always
+    /// dropped.
+    pub fn notebook_outputs(mut self, notebook_outputs: bool) -> Self {
+        self.notebook_outputs = notebook_outputs;
+        self
+    }
+
+    /// Only collect files the AI considers relevant to `query`, plus their direct imports.
+    /// Requires GROQ_API_KEY.
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = Some(query.into());
+        self
+    }
+
+    /// Only collect the `top_k` files that best match `query` lexically, without any LLM
+    pub fn rank(mut self, query: impl Into<String>, top_k: usize) -> Self {
+        self.rank = Some((query.into(), top_k));
+        self
+    }
+
+    /// Caps the estimated token count of the collected files
+    pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn large_file_threshold(mut self, bytes: u64) -> Self {
+        self.large_file_threshold = bytes;
+        self
+    }
+
+    /// Match default and [`ignore`](Self::ignore) directory names in a case-sensitive way
+    pub fn case_sensitive_dirs(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive_dirs = case_sensitive;
+        self
+    }
+
+    /// Only collect these language presets (see [`langs::LANGUAGES`]) instead of the default
+    /// extension list. [`langs::AUTO`] picks the languages dominant under the root.
+    pub fn lang<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.langs.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Leave out the extensions of these language presets
+    pub fn exclude_lang<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude_langs.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Also collect documentation such as READMEs and Markdown, with the root README first
+    pub fn with_docs(mut self, with_docs: bool) -> Self {
+        self.with_docs = with_docs;
+        self
+    }
+
+    /// Also collect configuration such as TOML, YAML, Dockerfiles and `.env.example`, with the
+    /// root build manifests first
+    pub fn with_config(mut self, with_config: bool) -> Self {
+        self.with_config = with_config;
+        self
+    }
+
+    /// Reuse rendered blocks of unchanged files from a cache under `dir` (see [`default_cache_dir`])
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self.cache_required = true;
+        self
+    }
+
+    /// Like [`cache_dir`](Self::cache_dir), but a cache that can't be opened, such as one under
+    /// a read-only home directory, is left out instead of failing [`build`](Self::build). The
+    /// error is kept in [`RepoToText::cache_error`].
+    pub fn cache_dir_if_available(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self.cache_required = false;
+        self
+    }
+
+    pub fn build(self) -> io::Result<RepoToText> {
+        let template = self
+            .template
+            .clone()
+            .unwrap_or_else(|| OutputTemplate::builtin(self.format));
+        let templates = Templates::new(&template)?;
+
+        let mut cache_error = None;
+        let cache = match &self.cache_dir {
+            Some(dir) => match Cache::open(
+                dir,
+                &self.root,
+                render_fingerprint(&self, &template),
+                self.file_header
+                    .as_ref()
+                    .is_some_and(FileHeader::uses_modified),
+            ) {
+                Ok(cache) => Some(cache),
+                // The cache only saves time, so rendering can go on without it
+                Err(e) if !self.cache_required => {
+                    cache_error = Some(e);
+                    None
+                }
+                Err(e) => {
+                    return Err(io::Error::new(
+                        e.kind(),
+                        format!("Failed to open cache in {}: {}", dir.display(), e),
+                    ))
+                }
+            },
+            None => None,
+        };
+
+        let spec = FilterSpec {
+            languages: langs::resolve(&self.langs, &self.root)?,
+            excluded_languages: langs::resolve(&self.exclude_langs, &self.root)?,
+            ignores: self.ignores,
+            includes: self.includes,
+            case_sensitive_dirs: self.case_sensitive_dirs,
+            with_docs: self.with_docs,
+            with_config: self.with_config,
+            include_generated: self.include_generated,
+        };
+
+        Ok(RepoToText {
+            canonical_root: fs::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone()),
+            cache,
+            cache_error,
+            filters: Filters::new(&spec),
+            lead_with_project_files: self.with_docs || self.with_config,
+            root: self.root,
+            templates,
+            notebook_outputs: self.notebook_outputs,
+            data_summary_threshold: self.data_summary_threshold,
+            max_file_size: self.max_file_size,
+            truncation: self.truncation,
+            line_numbers: self.line_numbers,
+            file_header: self.file_header,
+            path_prefix: self.path_prefix,
+            output_file: self.output_file,
+            query: self.query,
+            rank: self.rank,
+            max_tokens: self.max_tokens,
+            large_file_threshold: self.large_file_threshold,
+            prefix: self.prefix,
+            suffix: self.suffix,
+        })
+    }
+}
+
+/// Collects and renders the files of a repository
+pub struct RepoToText {
+    root: PathBuf,
+    /// `root` with `.` and symlinks resolved, for rules that look at directory names above a path
+    canonical_root: PathBuf,
+    filters: Filters,
+    templates: Templates,
+    notebook_outputs: bool,
+    data_summary_threshold: Option<u64>,
+    max_file_size: Option<u64>,
+    truncation: Truncation,
+    line_numbers: Option<LineNumbers>,
+    file_header: Option<FileHeader>,
+    path_prefix: Option<String>,
+    output_file: Option<PathBuf>,
+    query: Option<String>,
+    rank: Option<(String, usize)>,
+    max_tokens: Option<u64>,
+    large_file_threshold: u64,
+    prefix: String,
+    suffix: String,
+    cache: Option<Cache>,
+    /// Why a cache from [`RepoToTextBuilder::cache_dir_if_available`] couldn't be opened
+    cache_error: Option<io::Error>,
+    /// Put the root README and build manifests before everything else
+    lead_with_project_files: bool,
+}
+
+impl RepoToText {
+    pub fn builder() -> RepoToTextBuilder {
+        RepoToTextBuilder {
+            root: PathBuf::from("."),
+            ignores: Vec::new(),
+            includes: Vec::new(),
+            format: OutputFormat::default(),
+            query: None,
+            rank: None,
+            max_tokens: None,
+            large_file_threshold: DEFAULT_LARGE_FILE_THRESHOLD,
+            cache_dir: None,
+            cache_required: true,
+            case_sensitive_dirs: false,
+            langs: Vec::new(),
+            exclude_langs: Vec::new(),
+            with_docs: false,
+            with_config: false,
+            notebook_outputs: false,
+            include_generated: false,
+            data_summary_threshold: Some(DEFAULT_DATA_SUMMARY_THRESHOLD),
+            max_file_size: None,
+            truncation: Truncation::default(),
+            line_numbers: None,
+            file_header: None,
+            template: None,
+            path_prefix: None,
+            output_file: None,
+            prefix: String::new(),
+            suffix: String::new(),
+        }
+    }
+
+    pub fn filters(&self) -> &Filters {
+        &self.filters
+    }
+
+    /// Why the cache directory given to [`RepoToTextBuilder::cache_dir_if_available`] couldn't
+    /// be opened, in which case files are rendered without the cache
+    pub fn cache_error(&self) -> Option<&io::Error> {
+        self.cache_error.as_ref()
+    }
+
+    pub fn large_file_threshold(&self) -> u64 {
+        self.large_file_threshold
+    }
+
+    /// Whether `file` is a data file large enough to be summarised rather than included in full
+    pub fn summarizes(&self, file: &FileEntry) -> bool {
+        self.summarizes_data(&file.path, file.size)
+    }
+
+    /// Whether `file` is over the `--max-file-size` limit and will be truncated. Data files
+    /// that are summarised aren't truncated.
+    pub fn truncates(&self, file: &FileEntry) -> bool {
+        self.max_file_size.is_some_and(|max| file.size > max) && !self.summarizes(file)
+    }
+
+    /// A [`FileEntry`] for `path`, with the token estimate capped at what a truncated or
+    /// summarised file can render to
+    fn entry(&self, path: PathBuf) -> FileEntry {
+        let mut entry = FileEntry::new(path);
+        let rendered_bytes = if self.summarizes(&entry) {
+            Some(summarize::ESTIMATED_SUMMARY_BYTES)
+        } else if self.truncates(&entry) {
+            self.max_file_size
+        } else {
+            None
+        };
+        if let Some(bytes) = rendered_bytes {
+            entry.estimated_tokens = entry.estimated_tokens.min(estimate_tokens(bytes));
+        }
+        entry
+    }
+
+    fn summarizes_data(&self, path: &Path, size: u64) -> bool {
+        self.data_summary_threshold
+            .is_some_and(|threshold| size >= threshold)
+            && summarize::is_data_file(path)
+    }
+
+    /// Walks the root, applies the filters and any query or rank selection, then the token budget
+    pub fn collect(&self) -> io::Result<Vec<FileEntry>> {
+        Ok(self.apply_token_budget(self.collect_candidates()?))
+    }
+
+    /// Like [`collect`](Self::collect) but without applying the token budget, so callers can
+    /// drop files (e.g. large ones) before calling [`apply_token_budget`](Self::apply_token_budget)
+    pub fn collect_candidates(&self) -> io::Result<Vec<FileEntry>> {
+        Ok(self.collect_candidates_with_skips()?.0)
+    }
+
+    /// Like [`collect_candidates`](Self::collect_candidates), also returning every path that was
+    /// left out and why, sorted by path
+    pub fn collect_candidates_with_skips(&self) -> io::Result<(Vec<FileEntry>, Vec<Skipped>)> {
+        let (files, mut skipped) = self.collect_files(&self.root)?;
+
+        let selected = if let Some(query) = &self.query {
+            self.select_for_query(query, &files)?
+        } else if let Some((query, top_k)) = &self.rank {
+            self.select_by_rank(query, *top_k, &files)
+        } else {
+            let mut files: Vec<FileEntry> =
+                files.into_iter().map(|path| self.entry(path)).collect();
+            if self.lead_with_project_files {
+                files.sort_by_key(|f| {
+                    lead_priority(f.path.strip_prefix(&self.root).unwrap_or(&f.path))
+                });
+            }
+            return Ok((files, skipped));
+        };
+
+        let selected_paths: HashSet<&Path> = selected.iter().map(|f| f.path.as_path()).collect();
+        skipped.extend(
+            files
+                .iter()
+                .filter(|path| !selected_paths.contains(path.as_path()))
+                .map(|path| Skipped {
+                    path: path.clone(),
+                    reason: SkipReason::NotSelected,
+                }),
+        );
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok((selected, skipped))
+    }
+
+    /// Keeps files in order while they fit in the token budget, skipping any that would overflow it.
+    /// The prefix and suffix count against the budget first.
+    pub fn apply_token_budget(&self, files: Vec<FileEntry>) -> Vec<FileEntry> {
+        let Some(budget) = self.max_tokens else {
+            return files;
+        };
+
+        let mut used = self.prompt_tokens();
+        files
+            .into_iter()
+            .filter(|file| {
+                if used + file.estimated_tokens > budget {
+                    return false;
+                }
+                used += file.estimated_tokens;
+                true
+            })
+            .collect()
+    }
+
+    /// Estimated tokens of the prefix and suffix
+    pub fn prompt_tokens(&self) -> u64 {
+        estimate_tokens((self.prefix.len() + self.suffix.len()) as u64)
+    }
+
+    /// Collects the files and writes the rendered document to `writer`
+    pub fn render<W: Write>(&self, writer: W) -> io::Result<RenderStats> {
+        let files = self.collect()?;
+        self.render_files(&files, writer)
+    }
+
+    /// Writes the rendered document for `files` to `writer`
+    pub fn render_files<W: Write>(
+        &self,
+        files: &[FileEntry],
+        writer: W,
+    ) -> io::Result<RenderStats> {
+        self.render_files_with_progress(files, writer, |_, _| {})
+    }
+
+    /// Like [`render_files`](Self::render_files), calling `on_file` with the running count
+    /// and each file as it is processed
+    pub fn render_files_with_progress<W, F>(
+        &self,
+        files: &[FileEntry],
+        mut writer: W,
+        on_file: F,
+    ) -> io::Result<RenderStats>
+    where
+        W: Write,
+        F: Fn(usize, &FileEntry) + Sync,
+    {
+        let document = self.document_context(files);
+        let prefix = prompt_section(&self.prefix, "", "\n\n");
+        let suffix = prompt_section(&self.suffix, "\n", "\n");
+        writer.write_all(prefix.as_bytes())?;
+        writer.write_all(self.templates.render_header(&document)?.as_bytes())?;
+
+        let commits = LastCommits::new(
+            &self.root,
+            files
+                .iter()
+                .map(|f| {
+                    f.path
+                        .strip_prefix(&self.root)
+                        .unwrap_or(&f.path)
+                        .to_path_buf()
+                })
+                .collect(),
+        );
+        let processed_count = AtomicUsize::new(0);
+        let mut stats = RenderStats {
+            files: files.len(),
+            bytes: (prefix.len() + suffix.len()) as u64,
+            estimated_tokens: self.prompt_tokens(),
+            escaped_paths: files
+                .iter()
+                .filter(|f| !paths::is_utf8(&f.path))
+                .map(|f| f.path.clone())
+                .collect(),
+            ..RenderStats::default()
+        };
+        let (hits_before, misses_before) = self.cache_counts();
+
+        // Files are rendered in parallel into memory one batch at a time, then written in
+        // order, so memory stays bounded by the batch size rather than the repository size
+        for batch in render_batches(files) {
+            let blocks: Vec<Result<RenderedBlock, SkipReason>> = batch
+                .par_iter()
+                .map(|file| {
+                    let count = processed_count.fetch_add(1, Ordering::Relaxed) + 1;
+                    on_file(count, file);
+
+                    self.process_file(file, &commits)
+                })
+                .collect::<io::Result<_>>()?;
+
+            for (file, rendered) in batch.iter().zip(blocks) {
+                let rendered = match rendered {
+                    Ok(rendered) => rendered,
+                    Err(reason) => {
+                        stats.files -= 1;
+                        stats.skipped.push(Skipped {
+                            path: file.path.clone(),
+                            reason,
+                        });
+                        continue;
+                    }
+                };
+                writer.write_all(&rendered.block)?;
+                stats.bytes += rendered.block.len() as u64;
+                stats.estimated_tokens += rendered.tokens;
+            }
+        }
+
+        writer.write_all(self.templates.render_footer(&document)?.as_bytes())?;
+        writer.write_all(suffix.as_bytes())?;
+
+        let (hits_after, misses_after) = self.cache_counts();
+        stats.cache_hits = hits_after - hits_before;
+        stats.cache_misses = misses_after - misses_before;
+
+        writer.flush()?;
+        Ok(stats)
+    }
+
+    /// Variables for the header and footer templates
+    fn document_context(&self, files: &[FileEntry]) -> DocumentContext {
+        let paths: Vec<String> = files.iter().map(|f| self.output_path(&f.path)).collect();
+
+        DocumentContext {
+            generated_on: format!("{:?}", SystemTime::now()),
+            root: repo_name(&self.root),
+            files: files
+                .iter()
+                .zip(&paths)
+                .map(|(f, path)| FileSummary {
+                    path: path.clone(),
+                    language: header::language(&f.path),
+                    size: format_size(f.size),
+                    bytes: f.size,
+                    tokens: f.estimated_tokens,
+                })
+                .collect(),
+            file_count: files.len(),
+            total_size: format_size(files.iter().map(|f| f.size).sum()),
+            estimated_tokens: files.iter().map(|f| f.estimated_tokens).sum(),
+            tree: template::tree(&paths),
+        }
+    }
+
+    /// `path` as written to the output: relative to the root with forward slashes, behind
+    /// the path prefix if there is one
+    pub fn output_path(&self, path: &Path) -> String {
+        let relative = display_path(path.strip_prefix(&self.root).unwrap_or(path));
+        match &self.path_prefix {
+            Some(prefix) => format!("{}/{}", prefix, relative),
+            None => relative,
+        }
+    }
+
+    fn cache_counts(&self) -> (usize, usize) {
+        self.cache
+            .as_ref()
+            .map(|cache| (cache.hits(), cache.misses()))
+            .unwrap_or_default()
+    }
+
+    /// Whether a path relative to the root passes the directory and file filters.
+    /// The path does not need to exist, so this also applies to deleted files.
+    pub fn matches_filters(&self, relative: &Path) -> bool {
+        let dirs_allowed = relative
+            .parent()
+            .is_none_or(|parent| self.dirs_skip_reason(parent).is_none());
+
+        dirs_allowed && !self.is_output_file(relative) && self.should_collect_file(relative)
+    }
+
+    /// Whether `relative` is the file the document is written to
+    fn is_output_file(&self, relative: &Path) -> bool {
+        self.output_file
+            .as_deref()
+            .is_some_and(|output| self.canonical_root.join(relative) == output)
+    }
+
+    /// Whether the walk goes into `relative_dir`, a directory relative to the root, meaning
+    /// neither it nor any of its parents is pruned
+    pub fn walks_dir(&self, relative_dir: &Path) -> bool {
+        self.dirs_skip_reason(relative_dir).is_none()
+    }
+
+    /// The rule pruning `relative_dir` or any of its parents, checked from the root down
+    fn dirs_skip_reason(&self, relative_dir: &Path) -> Option<SkipReason> {
+        let mut dir = PathBuf::new();
+        for component in relative_dir.components() {
+            if let Component::Normal(name) = component {
+                dir.push(name);
+                if let Some(reason) = self.filters.dir_skip_reason(&self.canonical_root, &dir) {
+                    return Some(reason);
+                }
+            }
+        }
+        None
+    }
+
+    fn should_collect_file(&self, path: &Path) -> bool {
+        self.filters.file_skip_reason(path).is_none()
+    }
+
+    /// Explains why `relative`, a path under the root, would be left out by the filters or
+    /// for being binary or generated. `None` means the walk would collect it (or walk into it, for a directory).
+    pub fn explain(&self, relative: &Path) -> io::Result<Option<SkipReason>> {
+        let path = self.root.join(relative);
+        let metadata = fs::metadata(&path)?;
+
+        let dirs = if metadata.is_dir() {
+            Some(relative)
+        } else {
+            relative.parent()
+        };
+        if let Some(reason) = dirs.and_then(|dirs| self.dirs_skip_reason(dirs)) {
+            return Ok(Some(reason));
+        }
+        if metadata.is_dir() {
+            return Ok(None);
+        }
+        if self.is_output_file(relative) {
+            return Ok(Some(SkipReason::OutputFile));
+        }
+
+        if let Some(reason) = self.filters.file_skip_reason(relative) {
+            return Ok(Some(reason));
+        }
+
+        Ok(self.content_skip_reason(relative, &fs::read(&path)?))
+    }
+
+    /// Walks `dir`, returning the collected files and everything the filters skipped
+    fn collect_files(&self, dir: &Path) -> io::Result<(Vec<PathBuf>, Vec<Skipped>)> {
+        let mut skipped = Vec::new();
+
+        let entries: Vec<Result<PathBuf, Skipped>> = WalkDir::new(dir)
+            .into_iter()
+            // Skip entries whose parent directories are in the ignored list
+            .filter_entry(|entry| {
+                let path = entry.path();
+                if path.is_dir() {
+                    let relative = path.strip_prefix(dir).unwrap_or(path);
+                    if let Some(reason) =
+                        self.filters.dir_skip_reason(&self.canonical_root, relative)
+                    {
+                        skipped.push(Skipped {
+                            path: path.to_path_buf(),
+                            reason,
+                        });
+                        return false;
+                    }
+                }
+                true
+            })
+            // Convert the iterator to a parallel iterator
+            .par_bridge()
+            .filter_map(|entry| match entry {
+                Ok(e) => {
+                    let path = e.into_path();
+
+                    if !path.is_file() {
+                        return None;
+                    }
+
+                    if self.is_output_file(path.strip_prefix(dir).unwrap_or(&path)) {
+                        return Some(Err(Skipped {
+                            path,
+                            reason: SkipReason::OutputFile,
+                        }));
+                    }
+
+                    // Skip unwanted files
+                    match self.filters.file_skip_reason(&path) {
+                        Some(reason) => Some(Err(Skipped { path, reason })),
+                        None => Some(Ok(path)),
+                    }
+                }
+                Err(_) => None,
+            })
+            .collect();
+
+        let mut files = Vec::new();
+        for entry in entries {
+            match entry {
+                Ok(path) => files.push(path),
+                Err(skip) => skipped.push(skip),
+            }
+        }
+
+        // The parallel walk yields files in arbitrary order
+        files.sort();
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok((files, skipped))
+    }
+
+    /// Renders one file's block into memory, reusing the cached block if the file is unchanged,
+    /// or returns the [`SkipReason`] when its content turns out to be binary or generated, or
+    /// the file is gone
+    fn process_file(
+        &self,
+        file: &FileEntry,
+        commits: &LastCommits,
+    ) -> io::Result<Result<RenderedBlock, SkipReason>> {
+        // Read file as bytes instead of UTF-8 string
+        let looked_up = match &self.cache {
+            Some(cache) => cache.lookup(&file.path, file.size, file.modified, || {
+                fs::read(&file.path)
+            }),
+            None => fs::read(&file.path).map(Err),
+        };
+        let content = match looked_up {
+            Ok(Ok(cached)) => return Ok(Ok(cached)),
+            Ok(Err(content)) => content,
+            // Removed since the walk, e.g. by a branch switch or an editor's atomic save
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Err(SkipReason::Deleted)),
+            Err(e) => return Err(e),
+        };
+
+        // Skipped files are never cached, so a hit above is always kept
+        if let Some(reason) = self.content_skip_reason(&file.path, &content) {
+            return Ok(Err(reason));
+        }
+
+        let rendered = self.render_content(file, &content, commits)?;
+
+        if let Some(cache) = &self.cache {
+            cache.store(&file.path, file.size, file.modified, &content, &rendered);
+        }
+
+        Ok(Ok(rendered))
+    }
+
+    fn content_skip_reason(&self, path: &Path, content: &[u8]) -> Option<SkipReason> {
+        if render::is_binary(content) {
+            return Some(SkipReason::Binary);
+        }
+        if self.filters.include_generated() {
+            return None;
+        }
+        match generated::by_content(path, &String::from_utf8_lossy(content)) {
+            // Minified data is still data, and its summary is as small as any
+            Some(GeneratedSign::Minified { .. })
+                if self.summarizes_data(path, content.len() as u64) =>
+            {
+                None
+            }
+            sign => sign.map(SkipReason::Generated),
+        }
+    }
+
+    fn render_content(
+        &self,
+        file: &FileEntry,
+        raw: &[u8],
+        commits: &LastCommits,
+    ) -> io::Result<RenderedBlock> {
+        let path = file.path.as_path();
+        // Convert to string, replacing invalid UTF-8 with placeholder
+        let content = String::from_utf8_lossy(raw);
+        let size = content.len() as u64;
+        let content_lines = content.lines().count();
+
+        // Notebooks are JSON full of metadata and base64 images; render their cells instead
+        let is_notebook = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ipynb"));
+        let notebook = is_notebook
+            .then(|| notebook::render(&content, self.notebook_outputs))
+            .flatten();
+        let on_disk_lines = notebook.is_none();
+        let (content, lang) = match notebook {
+            Some(notebook) => (Cow::Owned(notebook.text), Some(notebook.language)),
+            None => (content, None),
+        };
+
+        let summary = self
+            .summarizes_data(path, size)
+            .then(|| summarize::summarize(path, &content))
+            .flatten();
+        let processed_content = match summary {
+            Some(summary) => summary,
+            None => {
+                // Numbered before anything removes lines, so the numbers match the file on disk
+                let (content, number_prefix) = match &self.line_numbers {
+                    Some(numbers) if on_disk_lines => {
+                        let (numbered, prefix) = numbers.apply(&content);
+                        (Cow::Owned(numbered), prefix)
+                    }
+                    _ => (content, 0),
+                };
+                let content = render::strip_binary_data(&content);
+                if let Some(max) = self.max_file_size.filter(|max| size > *max) {
+                    truncate::truncate(&content, self.truncation, number_prefix, max as usize)
+                } else {
+                    content
+                }
+            }
+        };
+
+        let output_path = self.output_path(path);
+        let metadata = self.file_header.as_ref().map(|header| {
+            header.render(&FileFacts {
+                root: &self.root,
+                path,
+                output_path: &output_path,
+                content: raw,
+                modified: file.modified,
+                tokens: estimate_tokens(processed_content.len() as u64),
+                commits,
+            })
+        });
+
+        let block = self
+            .templates
+            .render_file(&FileContext {
+                path: output_path,
+                language: header::language(path),
+                lang: lang.unwrap_or_else(|| {
+                    path.extension()
+                        .map(|e| e.to_string_lossy().to_lowercase())
+                        .unwrap_or_default()
+                }),
+                fence: template::fence_for(&processed_content),
+                content: &processed_content,
+                size: format_size(size),
+                bytes: size,
+                lines: content_lines,
+                tokens: estimate_tokens(processed_content.len() as u64),
+                metadata,
+            })?
+            .into_bytes();
+
+        Ok(RenderedBlock {
+            tokens: estimate_tokens(block.len() as u64),
+            block,
+        })
+    }
+
+    /// Keeps the files the AI ranks as relevant to `query`, each followed by the collected files it imports
+    fn select_for_query(&self, query: &str, files: &[PathBuf]) -> io::Result<Vec<FileEntry>> {
+        let entries: Vec<FileEntry> = files.iter().map(|path| self.entry(path.clone())).collect();
+        let sized: Vec<(PathBuf, u64)> = entries.iter().map(|e| (e.path.clone(), e.size)).collect();
+        let ranked = ai::get_ai_relevant_files(query, &sized)?;
+
+        let by_path: HashMap<PathBuf, &FileEntry> = entries
+            .iter()
+            .map(|e| (imports::normalize(&e.path), e))
+            .collect();
+
+        let mut seen: HashSet<&Path> = HashSet::new();
+        let mut selected: Vec<FileEntry> = Vec::new();
+
+        for name in ranked {
+            let Some(entry) = by_path.get(&imports::normalize(Path::new(&name))) else {
+                continue;
+            };
+            if !seen.insert(&entry.path) {
+                continue;
+            }
+            selected.push((*entry).clone());
+
+            let content = match fs::read(&entry.path) {
+                Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+                Err(_) => continue,
+            };
+            for candidate in imports::import_candidates(&entry.path, &content) {
+                if let Some(dep) = by_path.get(&imports::normalize(&candidate)) {
+                    if seen.insert(&dep.path) {
+                        selected.push(FileEntry {
+                            via_import: true,
+                            ..(*dep).clone()
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(selected)
+    }
+
+    /// Ranks `files` against `query` with BM25 and keeps the best `top_k`, best first
+    fn select_by_rank(&self, query: &str, top_k: usize, files: &[PathBuf]) -> Vec<FileEntry> {
+        let index = bm25::Index::build(files);
+
+        index
+            .search(query)
+            .into_iter()
+            .take(top_k)
+            .map(|(path, score)| FileEntry {
+                score: Some(score),
+                ..self.entry(path.to_path_buf())
+            })
+            .collect()
+    }
+}
+
+/// Identifies everything besides a file's content that affects its rendered block
+fn render_fingerprint(builder: &RepoToTextBuilder, template: &OutputTemplate) -> String {
+    // A new commit can change any file's last-commit fields without touching the file
+    let head = builder
+        .file_header
+        .as_ref()
+        .filter(|header| header.uses_git())
+        .and_then(|_| header::head_commit(&builder.root));
+
+    format!(
+        "{}:{}:{}:{}:{:?}:{:?}:{}:{:?}:{:?}:{:?}:{:?}",
+        env!("CARGO_PKG_VERSION"),
+        cache::content_hash(template.file.as_bytes()),
+        builder.notebook_outputs,
+        builder.include_generated,
+        builder.data_summary_threshold,
+        builder.max_file_size,
+        builder.truncation,
+        builder.line_numbers,
+        builder.file_header.as_ref().map(FileHeader::template),
+        head,
+        builder.path_prefix
+    )
+}
+
+/// Sort key putting the root README first and the root build manifests after it; everything
+/// else keeps its order
+fn lead_priority(relative: &Path) -> u8 {
+    let at_root = relative
+        .parent()
+        .is_none_or(|parent| parent.as_os_str().is_empty());
+    let name = relative
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+
+    if !at_root {
+        2
+    } else if name.to_uppercase().starts_with("README") {
+        0
+    } else if filters::MANIFEST_FILES.contains(&name.as_ref()) {
+        1
+    } else {
+        2
+    }
+}
+
+/// Splits `files` into consecutive batches of at most [`RENDER_BATCH_BYTES`] of source
+/// (a single larger file gets a batch of its own)
+fn render_batches(files: &[FileEntry]) -> Vec<&[FileEntry]> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut batch_bytes = 0;
+
+    for (i, file) in files.iter().enumerate() {
+        if i > start && batch_bytes + file.size > RENDER_BATCH_BYTES {
+            batches.push(&files[start..i]);
+            start = i;
+            batch_bytes = 0;
+        }
+        batch_bytes += file.size;
+    }
+    if start < files.len() {
+        batches.push(&files[start..]);
+    }
+
+    batches
+}
+
+/// Rough token estimate for a file of `bytes` bytes (about four bytes per token)
+pub fn estimate_tokens(bytes: u64) -> u64 {
+    bytes.div_ceil(4)
+}
+
+/// `text` with its surrounding whitespace trimmed, between `before` and `after`, or nothing
+/// when it is blank
+fn prompt_section(text: &str, before: &str, after: &str) -> String {
+    let text = text.trim();
+    if text.is_empty() {
+        return String::new();
+    }
+    format!("{}{}{}", before, text, after)
+}
+
+/// Formats a byte count for humans, e.g. `1.5KB` or `2.34MB`
+pub fn format_size(bytes: u64) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = 1024.0 * 1024.0;
+
+    match bytes as f64 {
+        b if b >= MB => format!("{:.2}MB", b / MB),
+        b if b >= KB => format!("{:.1}KB", b / KB),
+        b => format!("{}B", b),
+    }
+}
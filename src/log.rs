@@ -0,0 +1,56 @@
+//! Leveled status output on stderr, so stdout stays free for the rendered document.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only warnings and errors
+    Quiet,
+    /// Progress and status messages
+    Normal,
+    /// Everything, including per-file details
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+pub fn enabled(level: Verbosity) -> bool {
+    verbosity() >= level
+}
+
+/// Prints a status message to stderr unless running quietly
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Verbosity::Normal) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Prints a detail message to stderr when running verbosely
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Verbosity::Verbose) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Prints a warning to stderr at every verbosity
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        eprintln!("Warning: {}", format_args!($($arg)*));
+    };
+}
@@ -1,830 +1,979 @@
-use clap::Parser;
-use rayon::iter::ParallelBridge;
-use rayon::prelude::*;
-use regex::Regex;
-use serde::{Deserialize, Serialize};
+use clap::{
+    builder::{PossibleValue, PossibleValuesParser, TypedValueParser},
+    error::ErrorKind,
+    ArgAction, CommandFactory, Parser, Subcommand,
+};
+#[macro_use]
+mod log;
+mod clipboard;
+mod progress;
+mod watch;
+
+use clipboard::ClipboardMethod;
+use log::Verbosity;
+use progress::ProgressBar;
+use repo_to_text::{
+    absolute,
+    ai::{self, collect_extensions_and_dirs, get_ai_ignore_suggestions},
+    config::{AiStrategy, ProjectConfig},
+    default_cache_dir, display_path, estimate_tokens, format_size, langs, repo_name, FileEntry,
+    FileHeader, Filters, IgnoreKind, IgnoreRule, LineNumbers, OutputFormat, OutputTemplate,
+    RepoToText, RuleSource, SkipReason, Skipped, Truncation, BUILTIN_TEMPLATES,
+    DEFAULT_DATA_SUMMARY_THRESHOLD,
+};
+use watch::RepoWatcher;
+
 use std::{
-    collections::HashSet,
-    env,
+    collections::{BTreeMap, HashSet},
     fs::{self, File},
-    io::{self, BufWriter, Write},
+    io::{self, BufWriter, IsTerminal, Write},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
-    time::SystemTime,
+    process::ExitCode,
+    time::Instant,
 };
-use walkdir::WalkDir;
-
-const DEFAULT_ALLOWED_EXTS: &[&str] = &[
-    "ada",
-    "adb",
-    "ads",
-    "apex",
-    "as",
-    "asm",
-    "astro",
-    "bas",
-    "bat",
-    "c",
-    "cc",
-    "cbl",
-    "cl",
-    "clj",
-    "cljc",
-    "cljs",
-    "cls",
-    "cmake",
-    "coffee",
-    "cr",
-    "cs",
-    "cshtml",
-    "csh",
-    "css",
-    "cue",
-    "cxx",
-    "d",
-    "dart",
-    "edn",
-    "elm",
-    "erl",
-    "ex",
-    "exs",
-    "fs",
-    "fsi",
-    "fsx",
-    "fsscript",
-    "f",
-    "f03",
-    "f08",
-    "f77",
-    "f90",
-    "f95",
-    "gd",
-    "gemspec",
-    "gleam",
-    "glsl",
-    "go",
-    "gradle",
-    "graphql",
-    "gql",
-    "groovy",
-    "gvy",
-    "h",
-    "handlebars",
-    "hbs",
-    "hh",
-    "hpp",
-    "hs",
-    "hx",
-    "hxx",
-    "htm",
-    "html",
-    "hrl",
-    "hcl",
-    "ipynb",
-    "java",
-    "jl",
-    "js",
-    "jsx",
-    "json",
-    "kql",
-    "kt",
-    "kts",
-    "less",
-    "liquid",
-    "lua",
-    "m",
-    "mm",
-    "ml",
-    "mli",
-    "mjs",
-    "move",
-    "nim",
-    "nix",
-    "odin",
-    "php",
-    "phtml",
-    "pl",
-    "pm",
-    "proto",
-    "ps1",
-    "psm1",
-    "pug",
-    "purs",
-    "py",
-    "pyi",
-    "pyx",
-    "q",
-    "r",
-    "rb",
-    "rego",
-    "rs",
-    "s",
-    "sass",
-    "scala",
-    "sbt",
-    "scm",
-    "scss",
-    "sh",
-    "slim",
-    "sol",
-    "sql",
-    "styl",
-    "svelte",
-    "swift",
-    "tcl",
-    "tf",
-    "tfvars",
-    "thrift",
-    "ts",
-    "tsx",
-    "twig",
-    "v",
-    "vb",
-    "vba",
-    "vbs",
-    "vh",
-    "vue",
-    "wgsl",
-    "xml",
-    "zig",
-    "zsh",
-];
-
-const DEFAULT_IGNORED_DIRS: &[&str] = &[
-    "__pycache__",
-    "__snapshots__",
-    "_build",
-    "_output",
-    "angular",
-    "bazel-bin",
-    "bazel-out",
-    "bazel-testlogs",
-    "bin",
-    "bower_components",
-    "build",
-    "buck-out",
-    "cache",
-    "cmake-build-debug",
-    "cmake-build-release",
-    "coverage",
-    "dart_tool",
-    "debug",
-    "deriveddata",
-    "dist",
-    "env",
-    "git",
-    "gradle",
-    "hg",
-    "jspm_packages",
-    "logs",
-    "m2",
-    "mypy_cache",
-    "next",
-    "node_modules",
-    "nuxt",
-    "obj",
-    "out",
-    "output",
-    "parcel-cache",
-    "pnpm-store",
-    "pods",
-    "pytest_cache",
-    "release",
-    "reports",
-    "ruff_cache",
-    "serverless",
-    "storybook-static",
-    "svelte-kit",
-    "svn",
-    "target",
-    "temp",
-    "terraform",
-    "tmp",
-    "vendor",
-    "venv",
-    "vercel",
-    "vs",
-    "vscode",
-    "yarn",
-    "yarn_cache",
-];
-
-// Groq API structures
-#[derive(Serialize)]
-struct GroqMessage {
-    role: String,
-    content: String,
+
+/// Output path that sends the document to stdout
+const STDOUT_PATH: &str = "-";
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Extra directories or extensions to ignore even if they would normally be included. Can be space or comma separated.
+    #[arg(short, long, value_delimiter = ',', num_args = 1..)]
+    ignore: Option<Vec<String>>,
+    /// Directory names to ignore, matched exactly (case-sensitive, leading dots kept). `/build` only matches at the root and `frontend/dist` only inside `frontend`
+    #[arg(long, value_delimiter = ',', num_args = 1..)]
+    ignore_dir: Option<Vec<String>>,
+    /// File extensions to ignore, without also ignoring directories of the same name
+    #[arg(long, value_delimiter = ',', num_args = 1..)]
+    ignore_ext: Option<Vec<String>>,
+    /// File names to ignore, matched exactly, e.g. `.env` or `package-lock.json`
+    #[arg(long, value_delimiter = ',', num_args = 1..)]
+    ignore_file: Option<Vec<String>>,
+    /// Match default and --ignore directory names case-sensitively, so `Build` isn't ignored like `build`
+    #[arg(long)]
+    case_sensitive_dirs: bool,
+    /// Additional file extensions to include beyond the default list of programming languages. Can be space or comma separated.
+    #[arg(short = 'I', long, value_delimiter = ',', num_args = 1..)]
+    include: Option<Vec<String>>,
+    /// Only collect these language presets instead of the default extension list, or `auto` for the dominant languages in the repo
+    #[arg(long, value_delimiter = ',', num_args = 1.., value_parser = lang_names())]
+    lang: Option<Vec<String>>,
+    /// Leave out these language presets
+    #[arg(long, value_delimiter = ',', num_args = 1.., value_parser = lang_names())]
+    exclude_lang: Option<Vec<String>>,
+    /// Also include documentation (READMEs, Markdown, reStructuredText, AsciiDoc), with the README first
+    #[arg(long)]
+    with_docs: bool,
+    /// Also include configuration (TOML, YAML, INI, Dockerfiles, Makefiles, `.env.example`), with build manifests first
+    #[arg(long)]
+    with_config: bool,
+    /// Keep lockfiles, minified bundles, source maps and files marked `@generated` or `DO NOT EDIT`
+    #[arg(long)]
+    include_generated: bool,
+    /// Disable AI-powered ignore suggestions (requires GROQ_API_KEY env var when enabled)
+    #[arg(long)]
+    no_ai: bool,
+    /// How AI suggestions combine with --ignore and config ignores: `replace` skips the AI when --ignore is given, `merge` unions everything
+    #[arg(long, value_parser = ai_strategies())]
+    ai_strategy: Option<AiStrategy>,
+    /// List every effective ignore/include rule, including built-in defaults
+    #[arg(long)]
+    show_rules: bool,
+    /// After writing the output, list every skipped path and the rule that skipped it
+    #[arg(long)]
+    list_skipped: bool,
+    /// Print the files that would be included, with sizes and estimated tokens, without reading
+    /// their contents or writing the output
+    #[arg(long, visible_alias = "dry-run", conflicts_with_all = ["watch", "clipboard", "clipboard_only"])]
+    list: bool,
+    /// Print the --list output as JSON
+    #[arg(long, requires = "list")]
+    json: bool,
+    /// Ask the AI which files are relevant to this question and only output those plus their direct imports
+    #[arg(long, conflicts_with = "no_ai")]
+    query: Option<String>,
+    /// Rank files against these terms with BM25, entirely offline, and only output the best matches
+    #[arg(long, visible_alias = "grep-context", conflicts_with = "query")]
+    rank: Option<String>,
+    /// Number of files kept by --rank
+    #[arg(long, default_value_t = 20, requires = "rank")]
+    top_k: usize,
+    /// Output layout, `text` unless the project config sets a template
+    #[arg(long, value_parser = formats())]
+    format: Option<OutputFormat>,
+    /// Output template: `text`, `markdown`, or one defined under [templates] in the project config
+    #[arg(long, conflicts_with = "format")]
+    template: Option<String>,
+    /// Put this directory in front of every path in the output, or the repository's directory name when no value is given
+    #[arg(long, num_args = 0..=1, value_name = "PREFIX")]
+    path_prefix: Option<Option<String>>,
+    /// Keep the text outputs of Jupyter notebook cells (images and other binary outputs are always dropped)
+    #[arg(long)]
+    notebook_outputs: bool,
+    /// Summarise JSON, CSV, TSV, SQL and XML files of at least this many bytes instead of including them in full
+    #[arg(long, default_value_t = DEFAULT_DATA_SUMMARY_THRESHOLD, conflicts_with = "no_data_summary")]
+    data_summary_threshold: u64,
+    /// Always include data files in full
+    #[arg(long)]
+    no_data_summary: bool,
+    /// Metadata written under each file's heading, e.g. "{size}, {lines} lines, last changed {commit_date} by {author}". Placeholders: {path}, {language}, {size}, {lines}, {tokens}, {modified}, {author}, {commit}, {commit_date}, {hash}
+    #[arg(long, value_parser = FileHeader::parse)]
+    file_header: Option<FileHeader>,
+    /// Prefix every line of file content with its line number in the file on disk
+    #[arg(long)]
+    line_numbers: bool,
+    /// Minimum width line numbers are right-aligned to (they're always wide enough for the file)
+    #[arg(long, default_value_t = 0, requires = "line_numbers")]
+    line_number_width: usize,
+    /// Text between a line number and the line
+    #[arg(long, default_value = " | ", requires = "line_numbers")]
+    line_number_separator: String,
+    /// Truncate files larger than this many bytes instead of including them in full
+    #[arg(long)]
+    max_file_size: Option<u64>,
+    /// How --max-file-size truncates: `head[:LINES]`, `head-tail[:LINES]` (from each end), `tokens[:TOKENS]` or `outline[:LINES]` (head, then the declarations in the rest)
+    #[arg(long, default_value_t = Truncation::default(), requires = "max_file_size")]
+    truncate: Truncation,
+    /// Write the contents of this file at the top of the output, before the header and file blocks, such as instructions for the model
+    #[arg(long)]
+    prefix_file: Option<PathBuf>,
+    /// Write the contents of this file after the last file block
+    #[arg(long)]
+    suffix_file: Option<PathBuf>,
+    /// Write this text at the very end, after any --suffix-file, such as the question to ask
+    #[arg(long)]
+    prompt: Option<String>,
+    /// Leave out files once the estimated token count (about 4 bytes per token) would exceed this budget
+    #[arg(long)]
+    max_tokens: Option<u64>,
+    /// Directory for the cache of processed files [default: ~/.cache/repo_to_text]
+    #[arg(long, conflicts_with = "no_cache")]
+    cache_dir: Option<PathBuf>,
+    /// Reprocess every file instead of reusing cached output for unchanged files
+    #[arg(long)]
+    no_cache: bool,
+    /// Keep running and regenerate the output whenever a collected file changes
+    #[arg(long, conflicts_with = "query")]
+    watch: bool,
+    /// Also copy the output to the clipboard: `auto` (default), `system`, or `osc52` for remote terminals
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "auto")]
+    clipboard: Option<ClipboardMethod>,
+    /// Copy the output to the clipboard instead of writing the output file
+    #[arg(long)]
+    clipboard_only: bool,
+    /// Warn when the clipboard payload is larger than this many bytes
+    #[arg(long, default_value_t = 2 * 1024 * 1024)]
+    clipboard_warn_size: u64,
+    /// Where to write the output; `-` writes it to stdout
+    #[arg(short, long, default_value = "repo_content.txt")]
+    output: String,
+    /// Only print warnings and errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print more detail, such as every file written to the output
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
 }
 
-#[derive(Serialize)]
-struct GroqRequest {
-    messages: Vec<GroqMessage>,
-    model: String,
-    temperature: f32,
-    max_completion_tokens: u32,
-    top_p: f32,
-    stream: bool,
-    reasoning_effort: String,
+fn lang_names() -> PossibleValuesParser {
+    PossibleValuesParser::new(
+        langs::LANGUAGES
+            .iter()
+            .map(|lang| lang.name)
+            .chain([langs::AUTO]),
+    )
 }
 
-#[derive(Deserialize, Debug)]
-struct GroqStreamChoice {
-    delta: GroqDelta,
+/// `--format` values, listed here with their help while the library parses them
+fn formats() -> impl TypedValueParser<Value = OutputFormat> {
+    PossibleValuesParser::new([
+        PossibleValue::new("text").help("Plain text with `--- File: ... ---` separators"),
+        PossibleValue::new("markdown")
+            .help("Markdown with a heading and fenced code block per file"),
+    ])
+    .try_map(|name| name.parse::<OutputFormat>())
 }
 
-#[derive(Deserialize, Debug)]
-struct GroqDelta {
-    content: Option<String>,
+/// `--ai-strategy` values
+fn ai_strategies() -> impl TypedValueParser<Value = AiStrategy> {
+    PossibleValuesParser::new([
+        PossibleValue::new("replace").help("Only ask the AI when no --ignore is given"),
+        PossibleValue::new("merge")
+            .help("Always ask the AI and union its suggestions with CLI and config ignores"),
+    ])
+    .try_map(|name| name.parse::<AiStrategy>())
 }
 
-#[derive(Deserialize, Debug)]
-struct GroqStreamResponse {
-    choices: Vec<GroqStreamChoice>,
+#[derive(Subcommand)]
+enum Command {
+    /// Explain which rule collects or skips each path, without writing any output
+    Explain {
+        /// Paths relative to the current directory
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
 }
 
-/// Directories to skip during the initial scan (these are almost always noise)
-const SCAN_SKIP_DIRS: &[&str] = &[
-    "node_modules",
-    ".git",
-    ".hg",
-    ".svn",
-    "target",
-    "__pycache__",
-    ".venv",
-    "venv",
-    "env",
-    ".env",
-    "dist",
-    "build",
-    ".next",
-    ".nuxt",
-    "vendor",
-    ".cargo",
-    "deps",
-    ".deps",
-];
-
-/// Scans a directory and collects all unique file extensions and directory names
-/// Only scans top 3 levels and skips known dependency/build directories
-fn collect_extensions_and_dirs(dir: &Path) -> (HashSet<String>, HashSet<String>) {
-    let mut extensions: HashSet<String> = HashSet::new();
-    let mut directories: HashSet<String> = HashSet::new();
-
-    let skip_dirs: HashSet<&str> = SCAN_SKIP_DIRS.iter().copied().collect();
-
-    for entry in WalkDir::new(dir)
-        .max_depth(3) // Only scan top 3 levels
-        .into_iter()
-        .filter_entry(|e| {
-            // Skip known problematic directories
-            if e.file_type().is_dir() {
-                if let Some(name) = e.file_name().to_str() {
-                    let name_lower = name.to_lowercase();
-                    return !skip_dirs.contains(name_lower.as_str());
-                }
-            }
-            true
-        })
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
-
-        if path.is_dir() {
-            if let Some(dirname) = path.file_name() {
-                let name = dirname.to_string_lossy().to_lowercase();
-                // Skip hidden directories from collection (but we still add them for AI to consider)
-                if !name.is_empty() {
-                    directories.insert(name);
-                }
-            }
-        } else if path.is_file() {
-            if let Some(ext) = path.extension() {
-                let ext_str = ext.to_string_lossy().to_lowercase();
-                if !ext_str.is_empty() {
-                    extensions.insert(ext_str);
-                }
+impl Args {
+    fn writes_stdout(&self) -> bool {
+        self.output == STDOUT_PATH
+    }
+}
+
+/// Prints the effective rules and where each came from. Defaults are only listed when `all` is set.
+fn report_rules(filters: &Filters, all: bool) {
+    let rules = filters.ignore_rules();
+    let ignores: Vec<&IgnoreRule> = rules
+        .iter()
+        .filter(|rule| all || rule.source != RuleSource::Default)
+        .collect();
+
+    if !ignores.is_empty() {
+        eprintln!("Ignore rules:");
+        for rule in &ignores {
+            match rule.kind {
+                IgnoreKind::Any => eprintln!("  {} ({})", rule.pattern, rule.source),
+                kind => eprintln!("  {} ({}, {})", rule.pattern, kind, rule.source),
             }
         }
     }
 
-    (extensions, directories)
-}
+    if !all {
+        let defaults = rules.len() - ignores.len();
+        eprintln!(
+            "Using {} default ignore rules (pass --show-rules to list them)",
+            defaults
+        );
+    }
 
-/// Calls the Groq LLM API to get suggestions for what to ignore
-fn get_ai_ignore_suggestions(
-    extensions: &HashSet<String>,
-    directories: &HashSet<String>,
-) -> io::Result<Vec<String>> {
-    let api_key = match env::var("GROQ_API_KEY") {
-        Ok(key) => key,
-        Err(_) => {
-            eprintln!("Warning: GROQ_API_KEY not set, skipping AI-powered ignore suggestions");
-            return Ok(vec![]);
+    let includes = filters.include_rules();
+    if !includes.is_empty() {
+        eprintln!("Include rules (take precedence over ignores):");
+        for (ext, source) in includes {
+            eprintln!("  {} ({})", ext, source);
         }
-    };
+    }
 
-    // Build the list of items to send to the LLM
-    let mut items: Vec<String> = Vec::new();
+    let names =
+        |langs: &[&langs::Language]| -> Vec<&str> { langs.iter().map(|l| l.name).collect() };
+    if !filters.languages().is_empty() {
+        eprintln!("Languages: {}", names(filters.languages()).join(", "));
+    }
+    if !filters.excluded_languages().is_empty() {
+        eprintln!(
+            "Excluded languages: {}",
+            names(filters.excluded_languages()).join(", ")
+        );
+    }
 
-    for ext in extensions {
-        items.push(format!(".{}", ext));
+    if all {
+        eprintln!("Allowed extensions: {}", filters.allowed_exts().join(", "));
+        if !filters.allowed_files().is_empty() {
+            eprintln!("Allowed file names: {}", filters.allowed_files().join(", "));
+        }
     }
-    for dir in directories {
-        items.push(dir.clone());
+}
+
+/// Asks which files above `threshold` to keep and returns the ones that were deselected
+fn prompt_large_files(files: &[FileEntry], threshold: u64) -> io::Result<HashSet<PathBuf>> {
+    let large_files: Vec<(PathBuf, u64)> = files
+        .iter()
+        .filter(|f| f.size > threshold)
+        .map(|f| (f.path.clone(), f.size))
+        .collect();
+
+    if large_files.is_empty() {
+        return Ok(HashSet::new());
     }
 
-    if items.is_empty() {
-        return Ok(vec![]);
+    // Without a terminal to ask on (e.g. in a pipeline), keep everything
+    let term = dialoguer::console::Term::stderr();
+    if !term.is_term() || !io::stdin().is_terminal() {
+        warn!(
+            "including {} large files (>1MB) without confirmation, as there is no terminal to ask on",
+            large_files.len()
+        );
+        return Ok(HashSet::new());
     }
 
-    let prompt = format!(
-        "I am filtering a codebase with the following directories and file extensions for only files that are useful in understanding the function of the application. Which of these should I ignore? Send only a JSON array of strings back and nothing else.\n\n{}",
-        items.join(", ")
-    );
+    eprintln!("\nFound large files (>1MB). Use ↑↓ to navigate, Y/N to select, Enter when done:");
+    let items: Vec<String> = large_files
+        .iter()
+        .map(|(path, size)| {
+            format!(
+                "{} ({:.2}MB)",
+                display_path(path),
+                *size as f64 / (1024.0 * 1024.0)
+            )
+        })
+        .collect();
 
-    println!("Asking AI for smart ignore suggestions...");
-
-    let client = reqwest::blocking::Client::new();
-
-    let request = GroqRequest {
-        messages: vec![GroqMessage {
-            role: "user".to_string(),
-            content: prompt,
-        }],
-        model: "qwen/qwen3-32b".to_string(),
-        temperature: 0.6,
-        max_completion_tokens: 4096,
-        top_p: 0.95,
-        stream: true,
-        reasoning_effort: "default".to_string(),
-    };
+    let mut current_selection = vec![true; items.len()];
+    let mut current_index = 0;
+
+    loop {
+        // Clear screen and show current state
+        eprint!("\x1B[2J\x1B[1;1H");
+        eprintln!(
+            "Select files to include (Y/N for current item, ↑↓ to navigate, Enter to finish):\n"
+        );
 
-    let response = client
-        .post("https://api.groq.com/openai/v1/chat/completions")
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(&request)
-        .send()
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("HTTP request failed: {}", e)))?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().unwrap_or_default();
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("Groq API error ({}): {}", status, body),
-        ));
-    }
-
-    // Process streaming response
-    let mut full_content = String::new();
-
-    for line in response.text().unwrap_or_default().lines() {
-        let line = line.trim();
-        if line.is_empty() || line == "data: [DONE]" {
-            continue;
+        for (idx, item) in items.iter().enumerate() {
+            let prefix = if idx == current_index { ">" } else { " " };
+            let status = if current_selection[idx] { "Y" } else { "N" };
+            eprintln!("{} [{}] {}", prefix, status, item);
         }
 
-        if let Some(json_str) = line.strip_prefix("data: ") {
-            if let Ok(stream_response) = serde_json::from_str::<GroqStreamResponse>(json_str) {
-                for choice in stream_response.choices {
-                    if let Some(content) = choice.delta.content {
-                        full_content.push_str(&content);
+        // Get user input
+        if let Ok(key) = term.read_key() {
+            match key {
+                dialoguer::console::Key::Char('y') | dialoguer::console::Key::Char('Y') => {
+                    current_selection[current_index] = true;
+                    if current_index < items.len() - 1 {
+                        current_index += 1;
+                    }
+                }
+                dialoguer::console::Key::Char('n') | dialoguer::console::Key::Char('N') => {
+                    current_selection[current_index] = false;
+                    if current_index < items.len() - 1 {
+                        current_index += 1;
                     }
                 }
+                dialoguer::console::Key::ArrowUp if current_index > 0 => {
+                    current_index -= 1;
+                }
+                dialoguer::console::Key::ArrowDown if current_index < items.len() - 1 => {
+                    current_index += 1;
+                }
+                dialoguer::console::Key::Enter => {
+                    break;
+                }
+                _ => {}
             }
         }
     }
 
-    // Parse the JSON array from the response
-    // First, try to extract JSON array from the response (it might have extra text)
-    let json_start = full_content.find('[');
-    let json_end = full_content.rfind(']');
-
-    let suggestions: Vec<String> = match (json_start, json_end) {
-        (Some(start), Some(end)) if end > start => {
-            let json_str = &full_content[start..=end];
-            serde_json::from_str(json_str).unwrap_or_else(|e| {
-                eprintln!("Warning: Failed to parse AI response as JSON: {}", e);
-                eprintln!("Response was: {}", full_content);
-                vec![]
-            })
-        }
-        _ => {
-            eprintln!("Warning: Could not find JSON array in AI response");
-            eprintln!("Response was: {}", full_content);
-            vec![]
-        }
-    };
+    Ok(large_files
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !current_selection[*i])
+        .map(|(_, (path, _))| path)
+        .collect())
+}
 
-    if !suggestions.is_empty() {
-        println!("AI suggests ignoring: {:?}", suggestions);
+fn main() -> ExitCode {
+    // A detached copy of the binary that keeps the copied output on the clipboard
+    if clipboard::is_daemon() {
+        return match clipboard::run_daemon() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        };
     }
 
-    Ok(suggestions)
-}
+    let args = Args::parse();
 
-#[derive(Parser)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    /// Extra directories or extensions to ignore even if they would normally be included. Can be space or comma separated.
-    #[arg(short, long, value_delimiter = ',', num_args = 1..)]
-    ignore: Option<Vec<String>>,
-    /// Additional file extensions to include beyond the default list of programming languages. Can be space or comma separated.
-    #[arg(short = 'I', long, value_delimiter = ',', num_args = 1..)]
-    include: Option<Vec<String>>,
-    /// Disable AI-powered ignore suggestions (requires GROQ_API_KEY env var when enabled)
-    #[arg(long)]
-    no_ai: bool,
-}
+    if args.watch && args.writes_stdout() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--watch needs an output file and can't be used with `--output -`",
+            )
+            .exit();
+    }
 
-struct RepoProcessor {
-    output_file: String,
-    ignored_dirs: HashSet<String>,
-    allowed_exts: HashSet<String>,
-    temp_dir: PathBuf,
-    large_files: Arc<Mutex<Vec<(PathBuf, u64)>>>,
-    size_threshold: u64,
+    log::set_verbosity(match (args.quiet, args.verbose) {
+        (true, _) => Verbosity::Quiet,
+        (false, 0) => Verbosity::Normal,
+        (false, _) => Verbosity::Verbose,
+    });
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        // The reader went away, e.g. `repo_to_text -o - | head`; that isn't a failure
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
 
-impl RepoProcessor {
-    fn new(
-        additional_ignores: Option<Vec<String>>,
-        include_exts: Option<Vec<String>>,
-    ) -> io::Result<Self> {
-        let temp_dir = tempfile::tempdir()?.into_path();
-
-        let mut ignored_dirs: HashSet<String> = DEFAULT_IGNORED_DIRS
-            .iter()
-            .map(|d| d.trim_start_matches('.').to_lowercase())
-            .collect();
-
-        let mut allowed_exts: HashSet<String> = DEFAULT_ALLOWED_EXTS
-            .iter()
-            .map(|ext| ext.trim_start_matches('.').to_lowercase())
-            .collect();
+fn run(args: &Args) -> io::Result<()> {
+    let target_dir = Path::new(".");
+    let output_file = args.output.as_str();
+    let config = ProjectConfig::load(target_dir)?;
+
+    let ai_strategy = args
+        .ai_strategy
+        .or(config.ai_strategy)
+        .unwrap_or(AiStrategy::Replace);
+    let has_cli_ignores = args.ignore.is_some()
+        || args.ignore_dir.is_some()
+        || args.ignore_ext.is_some()
+        || args.ignore_file.is_some();
+
+    // Explicit ignores come first so they are the reported source when an item is repeated
+    let mut builder = RepoToText::builder()
+        .root(target_dir)
+        .format(args.format.unwrap_or_default())
+        .notebook_outputs(args.notebook_outputs)
+        .line_numbers(args.line_numbers.then(|| LineNumbers {
+            width: args.line_number_width,
+            separator: args.line_number_separator.clone(),
+        }))
+        .file_header(args.file_header.clone())
+        .path_prefix(
+            args.path_prefix
+                .clone()
+                .map(|prefix| prefix.unwrap_or_else(|| repo_name(target_dir))),
+        )
+        .output_file((!args.writes_stdout()).then(|| absolute(Path::new(output_file))))
+        .max_file_size(args.max_file_size)
+        .truncation(args.truncate)
+        .data_summary_threshold((!args.no_data_summary).then_some(args.data_summary_threshold))
+        .case_sensitive_dirs(args.case_sensitive_dirs)
+        .ignore(args.ignore.clone().unwrap_or_default())
+        .ignore_dir(args.ignore_dir.clone().unwrap_or_default())
+        .ignore_ext(args.ignore_ext.clone().unwrap_or_default())
+        .ignore_file(args.ignore_file.clone().unwrap_or_default())
+        .ignore_from(config.ignore, RuleSource::Config)
+        .ignore_kind_from(config.ignore_dir, IgnoreKind::Dir, RuleSource::Config)
+        .ignore_kind_from(config.ignore_ext, IgnoreKind::Ext, RuleSource::Config)
+        .ignore_kind_from(config.ignore_file, IgnoreKind::File, RuleSource::Config)
+        .include(args.include.clone().unwrap_or_default())
+        .include_from(config.include, RuleSource::Config)
+        .lang(args.lang.clone().unwrap_or_default())
+        .lang(config.lang)
+        .exclude_lang(args.exclude_lang.clone().unwrap_or_default())
+        .exclude_lang(config.exclude_lang)
+        .with_docs(args.with_docs || config.with_docs)
+        .with_config(args.with_config || config.with_config)
+        .include_generated(args.include_generated || config.include_generated);
+
+    // With the replace strategy, explicit ignores disable the AI as before
+    let use_ai = !args.no_ai && (ai_strategy == AiStrategy::Merge || !has_cli_ignores);
+    if use_ai && !ai::has_api_key() {
+        warn!("GROQ_API_KEY not set, skipping AI-powered ignore suggestions");
+    } else if use_ai {
+        info!("Scanning directory for extensions and folders...");
+        let (extensions, directories) = collect_extensions_and_dirs(target_dir);
 
-        // Add user-provided extensions to ignore, if any
-        if let Some(additional) = additional_ignores {
-            for item in additional {
-                let cleaned = item.trim();
-                if cleaned.is_empty() {
-                    continue;
-                }
+        info!(
+            "Found {} unique extensions and {} directories",
+            extensions.len(),
+            directories.len()
+        );
 
-                let dir_name = cleaned.trim_start_matches('.').to_lowercase();
-                if !dir_name.is_empty() {
-                    ignored_dirs.insert(dir_name.clone());
-                    allowed_exts.remove(&dir_name);
+        info!("Asking AI for smart ignore suggestions...");
+        match get_ai_ignore_suggestions(&extensions, &directories) {
+            Ok(suggestions) => {
+                if !suggestions.is_empty() {
+                    info!("AI suggests ignoring: {:?}", suggestions);
                 }
+                builder = builder.ignore_from(suggestions, RuleSource::Ai);
             }
-        }
-
-        if let Some(includes) = include_exts {
-            for item in includes {
-                let cleaned = item.trim();
-                if cleaned.is_empty() {
-                    continue;
-                }
-
-                let ext = cleaned.trim_start_matches('.').to_lowercase();
-                if !ext.is_empty() {
-                    allowed_exts.insert(ext);
-                }
+            Err(e) => {
+                warn!("AI suggestion failed: {}", e);
             }
         }
-
-        Ok(Self {
-            output_file: "repo_content.txt".to_string(),
-            ignored_dirs,
-            allowed_exts,
-            temp_dir,
-            large_files: Arc::new(Mutex::new(Vec::new())),
-            size_threshold: 1024 * 1024, // 1MB in bytes
-        })
     }
 
-    fn should_ignore_dir(&self, dir: &str) -> bool {
-        let dir_lower = dir.to_lowercase();
-        let dir_clean = dir_lower.trim_start_matches('.');
-        self.ignored_dirs.contains(dir_clean)
+    if let Some(query) = &args.query {
+        builder = builder.query(query);
     }
-
-    fn should_ignore_ext(&self, file: &Path) -> bool {
-        let filename = file
-            .file_name()
-            .map(|f| f.to_string_lossy())
-            .unwrap_or_default();
-
-        // Check if file has no extension
-        if !filename.contains('.') || filename.ends_with('.') {
-            return true;
+    if let Some(rank) = &args.rank {
+        builder = builder.rank(rank, args.top_k);
+    }
+    if let Some(max_tokens) = args.max_tokens {
+        builder = builder.max_tokens(max_tokens);
+    }
+    if let Some(path) = &args.prefix_file {
+        builder = builder.prefix(read_prompt_file(path)?);
+    }
+    // The suffix file, then the prompt, a blank line apart
+    let mut suffix = match &args.suffix_file {
+        Some(path) => read_prompt_file(path)?.trim_end().to_string(),
+        None => String::new(),
+    };
+    if let Some(prompt) = &args.prompt {
+        if !suffix.is_empty() {
+            suffix.push_str("\n\n");
         }
+        suffix.push_str(prompt.trim());
+    }
+    builder = builder.suffix(suffix);
+    // --template, then --format, then the config's default template
+    let template_name = args
+        .template
+        .as_deref()
+        .or(config.template.as_deref().filter(|_| args.format.is_none()));
+    if let Some(name) = template_name {
+        builder = builder.template(resolve_template(name, &config.templates)?);
+    }
 
-        // Check for .so.* pattern (case-insensitive)
-        if filename.to_lowercase().contains(".so.") {
-            return true;
+    // Only an explicit --cache-dir has to work; the default one is skipped if it can't be used
+    let cache_dir = if args.no_cache {
+        None
+    } else if let Some(cache_dir) = &args.cache_dir {
+        builder = builder.cache_dir(cache_dir);
+        Some(cache_dir.clone())
+    } else {
+        let cache_dir = default_cache_dir();
+        if let Some(cache_dir) = &cache_dir {
+            builder = builder.cache_dir_if_available(cache_dir);
         }
+        cache_dir
+    };
 
-        let extension = match file.extension() {
-            Some(ext) => ext.to_string_lossy().to_string().to_lowercase(),
-            None => return true,
-        };
-
-        if extension.is_empty() {
-            return true;
+    let repo = builder.build()?;
+    if let Some(cache_dir) = &cache_dir {
+        match repo.cache_error() {
+            Some(e) => {
+                warn!(
+                    "can't use the cache in {}: {}; processing every file",
+                    cache_dir.display(),
+                    e
+                );
+            }
+            None => {
+                debug!("Using cache in {}", cache_dir.display());
+            }
         }
-
-        !self.allowed_exts.contains(&extension)
+    }
+    // An explicit --show-rules is printed even when quiet
+    if args.show_rules || log::enabled(Verbosity::Normal) {
+        report_rules(repo.filters(), args.show_rules);
     }
 
-    fn collect_files(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
-        let files: Vec<PathBuf> = WalkDir::new(dir)
-            .into_iter()
-            // Skip entries whose parent directories are in the ignored list
-            .filter_entry(|entry| {
-                let path = entry.path();
-                if path.is_dir() {
-                    if let Some(dirname) = path.file_name() {
-                        return !self.should_ignore_dir(&dirname.to_string_lossy());
-                    }
-                }
-                true
-            })
-            // Convert the iterator to a parallel iterator
-            .par_bridge()
-            .filter_map(|entry| match entry {
-                Ok(e) => {
-                    let path = e.into_path();
-
-                    if !path.is_file() {
-                        return None;
-                    }
-
-                    // Skip unwanted files
-                    if let Some(filename) = path.file_name() {
-                        if filename.to_string_lossy().starts_with("._")
-                            || self.should_ignore_ext(&path)
-                        {
-                            return None;
-                        }
-                    }
+    if let Some(Command::Explain { paths }) = &args.command {
+        return explain(&repo, target_dir, paths);
+    }
+    if args.list {
+        return list_files(&repo, args.json);
+    }
 
-                    // Track large files
-                    if let Ok(metadata) = path.metadata() {
-                        let size = metadata.len();
-                        if size > self.size_threshold {
-                            self.large_files.lock().unwrap().push((path.clone(), size));
-                        }
-                    }
+    let mut rejected_large = None;
+    generate(&repo, args, output_file, &mut rejected_large)?;
 
-                    Some(path)
-                }
-                Err(_) => None,
-            })
-            .collect();
-
-        Ok(files)
+    if args.watch {
+        let mut watcher = RepoWatcher::new(target_dir, &[PathBuf::from(output_file)], &repo)?;
+        loop {
+            info!("\nWatching for changes (Ctrl-C to stop)...");
+            let changed = watcher.wait(&repo)?;
+            let names: Vec<String> = changed.iter().map(|p| display_path(p)).collect();
+            info!("Changed: {}", names.join(", "));
+            // A failed rerun, e.g. mid branch switch, is retried on the next change
+            if let Err(e) = generate(&repo, args, output_file, &mut rejected_large) {
+                warn!("failed to regenerate the output: {}", e);
+            }
+        }
     }
 
-    fn process_file(&self, file: &Path) -> io::Result<PathBuf> {
-        let hash = format!("{:x}", md5::compute(file.to_string_lossy().as_bytes()));
-        let outfile = self.temp_dir.join(format!("{}.txt", hash));
-
-        let mut writer = BufWriter::new(File::create(&outfile)?);
+    Ok(())
+}
 
-        writeln!(writer, "===============================================")?;
-        writeln!(writer, "--- File: {} ---", file.display())?;
-        writeln!(writer, "===============================================")?;
-        writeln!(writer)?;
+fn read_prompt_file(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to read {}: {}", path.display(), e),
+        )
+    })
+}
 
-        // Read file as bytes instead of UTF-8 string
-        let content = fs::read(file)?;
+/// Finds a template in the project config, falling back to the built-in ones
+fn resolve_template(
+    name: &str,
+    templates: &BTreeMap<String, OutputTemplate>,
+) -> io::Result<OutputTemplate> {
+    if let Some(template) = templates.get(name) {
+        return Ok(template.clone());
+    }
+    OutputTemplate::named(name).ok_or_else(|| {
+        let mut known: Vec<&str> = BUILTIN_TEMPLATES.to_vec();
+        known.extend(templates.keys().map(String::as_str));
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Unknown template \"{}\" (expected {})",
+                name,
+                known.join(", ")
+            ),
+        )
+    })
+}
 
-        // Convert to string, replacing invalid UTF-8 with placeholder
-        let content = String::from_utf8_lossy(&content);
+fn output_error(output_file: &str, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("Failed to write {}: {}", output_file, e))
+}
 
-        // Process content with the same patterns as the fish script
-        let binary_patterns = [
-            (
-                r#"(?s)(DATA = b""")[^"]*?(""")"#,
-                r#"$1<binary data removed>$2"#,
-            ),
-            (
-                r#"(?s)(b85decode\().*?(\))"#,
-                r#"$1"<binary data removed>"$2"#,
-            ),
-            (
-                r#"(?s)(base64\.[^(]*decode\().*?(\))"#,
-                r#"$1"<binary data removed>"$2"#,
-            ),
-        ];
+/// Copies the rendered output to the clipboard. Failing to copy is only fatal with --clipboard-only.
+fn copy_to_clipboard(rendered: &[u8], method: ClipboardMethod, args: &Args) -> io::Result<()> {
+    let size = rendered.len() as u64;
+    if size > args.clipboard_warn_size {
+        warn!(
+            "clipboard payload is {}, above the {} warning size; chat inputs and terminals may truncate it",
+            format_size(size),
+            format_size(args.clipboard_warn_size)
+        );
+    }
 
-        let processed_content =
-            binary_patterns
-                .iter()
-                .fold(content.to_string(), |acc, (pattern, replacement)| {
-                    Regex::new(pattern)
-                        .unwrap()
-                        .replace_all(&acc, *replacement)
-                        .to_string()
-                });
+    match clipboard::copy(&String::from_utf8_lossy(rendered), method) {
+        Ok(ClipboardMethod::Osc52) => {
+            info!("Output sent to the terminal clipboard via OSC 52");
+            Ok(())
+        }
+        Ok(_) => {
+            info!("Output copied to the clipboard");
+            Ok(())
+        }
+        Err(e) if !args.clipboard_only => {
+            warn!("failed to copy output to the clipboard: {}", e);
+            Ok(())
+        }
+        Err(e) => Err(io::Error::new(
+            e.kind(),
+            format!("Failed to copy output to the clipboard: {}", e),
+        )),
+    }
+}
 
-        write!(writer, "{}", processed_content)?;
-        writeln!(writer)?;
-        writeln!(writer, "--- End of File ---")?;
-        writeln!(writer)?;
-        writeln!(writer, "===============================================")?;
+/// Collects, selects and renders the repository once. Large files the user deselects are
+/// remembered in `rejected_large` so that watch-mode reruns don't prompt again.
+fn generate(
+    repo: &RepoToText,
+    args: &Args,
+    output_file: &str,
+    rejected_large: &mut Option<HashSet<PathBuf>>,
+) -> io::Result<()> {
+    let started = Instant::now();
+
+    info!("Collecting files...");
+    if args.query.is_some() {
+        info!("Asking AI which files are relevant to the query...");
+    }
+    let (files, mut skipped) = repo.collect_candidates_with_skips()?;
 
-        Ok(outfile)
+    if args.query.is_some() {
+        info!(
+            "AI selected {} relevant files, {} with their direct imports",
+            files.iter().filter(|f| !f.via_import).count(),
+            files.len()
+        );
     }
 
-    fn prompt_large_files(&self, files: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
-        if self.large_files.lock().unwrap().is_empty() {
-            return Ok(files.to_vec());
+    if let Some(rank) = &args.rank {
+        info!("Top {} files for \"{}\":", files.len(), rank);
+        for file in &files {
+            info!(
+                "  {:>8.3}  {}",
+                file.score.unwrap_or_default(),
+                display_path(&file.path)
+            );
         }
+    }
 
-        println!("\nFound large files (>1MB). Use ↑↓ to navigate, Y/N to select, Enter when done:");
-        let items: Vec<String> = self
-            .large_files
-            .lock()
-            .unwrap()
-            .iter()
-            .map(|(path, size)| {
-                format!(
-                    "{} ({:.2}MB)",
-                    path.display(),
-                    *size as f64 / (1024.0 * 1024.0)
-                )
-            })
-            .collect();
+    // Prompt for large files before processing
+    let threshold = repo.large_file_threshold();
+    let rejected = match rejected_large {
+        Some(rejected) => rejected,
+        None => {
+            // Summarised and truncated files stay small whatever their size, so there's nothing to ask
+            let unsummarized: Vec<FileEntry> = files
+                .iter()
+                .filter(|f| !repo.summarizes(f) && !repo.truncates(f))
+                .cloned()
+                .collect();
+            rejected_large.insert(prompt_large_files(&unsummarized, threshold)?)
+        }
+    };
+    let (files, too_large): (Vec<FileEntry>, Vec<FileEntry>) =
+        files.into_iter().partition(|f| !rejected.contains(&f.path));
+    skipped.extend(too_large.into_iter().map(|f| Skipped {
+        path: f.path,
+        reason: SkipReason::TooLarge {
+            size: f.size,
+            threshold,
+        },
+    }));
+
+    let candidate_count = files.len();
+    let candidates: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+    let files_to_process = repo.apply_token_budget(files);
+    if files_to_process.len() < candidate_count {
+        let kept: HashSet<&PathBuf> = files_to_process.iter().map(|f| &f.path).collect();
+        skipped.extend(
+            candidates
+                .into_iter()
+                .filter(|path| !kept.contains(path))
+                .map(|path| Skipped {
+                    path,
+                    reason: SkipReason::TokenBudget,
+                }),
+        );
+    }
 
-        let mut current_selection = vec![true; items.len()];
-        let mut current_index = 0;
+    if let Some(budget) = args.max_tokens {
+        let used: u64 = repo.prompt_tokens()
+            + files_to_process
+                .iter()
+                .map(|f| f.estimated_tokens)
+                .sum::<u64>();
+        info!(
+            "Token budget: ~{} of {} tokens used, {} files left out",
+            used,
+            budget,
+            candidate_count - files_to_process.len()
+        );
+    }
 
-        loop {
-            // Clear screen and show current state
-            print!("\x1B[2J\x1B[1;1H");
-            println!("Select files to include (Y/N for current item, ↑↓ to navigate, Enter to finish):\n");
-
-            for (idx, item) in items.iter().enumerate() {
-                let prefix = if idx == current_index { ">" } else { " " };
-                let status = if current_selection[idx] { "Y" } else { "N" };
-                println!("{} [{}] {}", prefix, status, item);
-            }
+    let total_files = files_to_process.len();
+    info!("Processing {} files...", total_files);
+    for file in &files_to_process {
+        debug!(
+            "  {} (~{} tokens)",
+            display_path(&file.path),
+            file.estimated_tokens
+        );
+    }
 
-            // Get user input
-            if let Ok(key) = dialoguer::console::Term::stdout().read_key() {
-                match key {
-                    dialoguer::console::Key::Char('y') | dialoguer::console::Key::Char('Y') => {
-                        current_selection[current_index] = true;
-                        if current_index < items.len() - 1 {
-                            current_index += 1;
-                        }
-                    }
-                    dialoguer::console::Key::Char('n') | dialoguer::console::Key::Char('N') => {
-                        current_selection[current_index] = false;
-                        if current_index < items.len() - 1 {
-                            current_index += 1;
-                        }
-                    }
-                    dialoguer::console::Key::ArrowUp if current_index > 0 => {
-                        current_index -= 1;
-                    }
-                    dialoguer::console::Key::ArrowDown if current_index < items.len() - 1 => {
-                        current_index += 1;
-                    }
-                    dialoguer::console::Key::Enter => {
-                        break;
-                    }
-                    _ => {}
-                }
+    let total_bytes = files_to_process.iter().map(|f| f.size).sum();
+    let bar = if log::enabled(Verbosity::Normal) {
+        ProgressBar::new(total_files, total_bytes)
+    } else {
+        ProgressBar::hidden(total_files, total_bytes)
+    };
+    let progress = |_: usize, file: &FileEntry| bar.inc(&file.path, file.size);
+    let end_progress = || bar.finish();
+
+    let clipboard_method = args
+        .clipboard
+        .or(args.clipboard_only.then_some(ClipboardMethod::Auto));
+
+    let stats = match clipboard_method {
+        Some(method) => {
+            // The clipboard needs the whole document in memory anyway
+            let mut rendered = Vec::new();
+            let stats =
+                repo.render_files_with_progress(&files_to_process, &mut rendered, progress)?;
+            end_progress();
+
+            if args.writes_stdout() && !args.clipboard_only {
+                io::stdout().lock().write_all(&rendered)?;
+            } else if !args.clipboard_only {
+                fs::write(output_file, &rendered).map_err(|e| output_error(output_file, e))?;
+                info!("Output saved to {}", output_file);
             }
+            copy_to_clipboard(&rendered, method, args)?;
+            stats
         }
+        None if args.writes_stdout() => {
+            let output = BufWriter::new(io::stdout().lock());
+            let stats = repo.render_files_with_progress(&files_to_process, output, progress)?;
+            end_progress();
+            stats
+        }
+        None => {
+            let output = BufWriter::new(
+                File::create(output_file).map_err(|e| output_error(output_file, e))?,
+            );
+            let stats = repo.render_files_with_progress(&files_to_process, output, progress)?;
+            end_progress();
+            info!("Output saved to {}", output_file);
+            stats
+        }
+    };
 
-        let selected_paths: HashSet<_> = self
-            .large_files
-            .lock()
-            .unwrap()
-            .iter()
-            .enumerate()
-            .filter(|(i, _)| current_selection[*i])
-            .map(|(_, (path, _))| path.clone())
-            .collect();
-
-        Ok(files
-            .iter()
-            .filter(|f| {
-                if self
-                    .large_files
-                    .lock()
-                    .unwrap()
-                    .iter()
-                    .any(|(p, _)| p == *f)
-                {
-                    selected_paths.contains(*f)
-                } else {
-                    true
-                }
-            })
-            .cloned()
-            .collect())
-    }
-
-    pub fn process_repository(&self) -> io::Result<()> {
-        let mut output = BufWriter::new(File::create(&self.output_file)?);
-
-        writeln!(output, "Repository Content Extraction")?;
-        writeln!(output, "Generated on: {:?}", SystemTime::now())?;
-        writeln!(output, "=================================================")?;
-        writeln!(output)?;
-
-        println!("Collecting files...");
-        let files = self.collect_files(Path::new("."))?;
-
-        // Prompt for large files before processing
-        let files_to_process = self.prompt_large_files(&files)?;
-        let total_files = files_to_process.len();
-
-        println!("Processing {} files...", total_files);
-        let processed_count = Arc::new(Mutex::new(0));
-        let output_mutex = Arc::new(Mutex::new(BufWriter::new(File::create(&self.output_file)?)));
-
-        // Process files in parallel using rayon's parallel iterator
-        files_to_process
-            .par_iter()
-            .try_for_each(|file| -> io::Result<()> {
-                let count = {
-                    let mut count = processed_count.lock().unwrap();
-                    *count += 1;
-                    *count
-                };
-
-                print!(
-                    "\rProcessing file {} of {}: {}",
-                    count,
-                    total_files,
-                    file.display()
-                );
-                io::stdout().flush()?;
+    for path in &stats.escaped_paths {
+        warn!(
+            "{} is not a valid UTF-8 path; it is written with its invalid bytes escaped",
+            display_path(path)
+        );
+    }
 
-                let temp_file = self.process_file(file)?;
-                let content = fs::read_to_string(&temp_file)?;
+    info!(
+        "Included {} files, {} of output (~{} tokens) in {:.2}s",
+        stats.files,
+        format_size(stats.bytes),
+        stats.estimated_tokens,
+        started.elapsed().as_secs_f64()
+    );
 
-                // Write directly to the output file under lock
-                let mut output = output_mutex.lock().unwrap();
-                write!(output, "{}", content)?;
+    let lookups = stats.cache_hits + stats.cache_misses;
+    if lookups > 0 {
+        info!(
+            "Cache: {} hits, {} misses ({:.1}% hit rate)",
+            stats.cache_hits,
+            stats.cache_misses,
+            stats.cache_hits as f64 * 100.0 / lookups as f64
+        );
+    }
 
-                // Clean up temp file immediately
-                fs::remove_file(temp_file)?;
+    skipped.extend(stats.skipped);
+    skipped.sort_by(|a, b| a.path.cmp(&b.path));
 
-                Ok(())
-            })?;
+    let summary = skip_summary(&skipped);
+    if !summary.is_empty() {
+        info!("Skipped {}", summary.join(", "));
+    }
 
-        println!(
-            "\nFinished processing. Output saved to {}",
-            self.output_file
-        );
+    if args.list_skipped {
+        for skip in &skipped {
+            eprintln!("  {}: {}", display_path(&skip.path), skip.reason);
+        }
+    }
 
-        // Cleanup temp directory
-        fs::remove_dir_all(&self.temp_dir)?;
+    Ok(())
+}
 
-        Ok(())
+/// Counts skipped paths by kind of reason, e.g. `3 binary files`
+fn skip_summary(skipped: &[Skipped]) -> Vec<String> {
+    let kinds = [
+        ("ignored directory", "ignored directories"),
+        ("file by name or extension", "files by name or extension"),
+        ("binary file", "binary files"),
+        ("generated file", "generated files"),
+        ("large file", "large files"),
+        ("file over the token budget", "files over the token budget"),
+        ("file not selected", "files not selected"),
+        ("deleted file", "deleted files"),
+    ];
+    let mut counts = [0; 8];
+    for skip in skipped {
+        let kind = match skip.reason {
+            SkipReason::IgnoredDir { .. } => 0,
+            SkipReason::AppleDouble
+            | SkipReason::SharedLibrary
+            | SkipReason::NoExtension
+            | SkipReason::IgnoredExt { .. }
+            | SkipReason::IgnoredFile { .. }
+            | SkipReason::ExcludedLanguage { .. }
+            | SkipReason::ExtNotAllowed { .. }
+            | SkipReason::OutputFile => 1,
+            SkipReason::Binary => 2,
+            SkipReason::Generated(_) => 3,
+            SkipReason::TooLarge { .. } => 4,
+            SkipReason::TokenBudget => 5,
+            SkipReason::NotSelected => 6,
+            SkipReason::Deleted => 7,
+        };
+        counts[kind] += 1;
     }
+
+    kinds
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|((one, many), count)| format!("{} {}", count, if count == 1 { one } else { many }))
+        .collect()
 }
 
-fn main() -> io::Result<()> {
-    let args = Args::parse();
+/// Prints whether each of `paths` would be collected, and if not, which rule skips it. Paths
+/// that can't be explained are reported on stderr and fail the command once all are done.
+fn explain(repo: &RepoToText, root: &Path, paths: &[PathBuf]) -> io::Result<()> {
+    let root = fs::canonicalize(root)?;
 
-    // Determine ignore patterns
-    let ignore_patterns = if args.ignore.is_some() {
-        // User provided explicit ignores, use those
-        args.ignore
-    } else if !args.no_ai {
-        // No explicit ignores and AI is enabled, get suggestions
-        let target_dir = Path::new(".");
-        println!("Scanning directory for extensions and folders...");
-        let (extensions, directories) = collect_extensions_and_dirs(target_dir);
+    let mut failed = 0;
+    for path in paths {
+        if let Err(e) = explain_path(repo, &root, path) {
+            eprintln!("{}: {}", path.display(), e);
+            failed += 1;
+        }
+    }
 
-        println!(
-            "Found {} unique extensions and {} directories",
-            extensions.len(),
-            directories.len()
-        );
+    if failed > 0 {
+        return Err(io::Error::other(format!(
+            "{} of {} paths couldn't be explained",
+            failed,
+            paths.len()
+        )));
+    }
+    Ok(())
+}
 
-        match get_ai_ignore_suggestions(&extensions, &directories) {
-            Ok(suggestions) if !suggestions.is_empty() => Some(suggestions),
-            Ok(_) => None,
-            Err(e) => {
-                eprintln!("Warning: AI suggestion failed: {}", e);
-                None
+fn explain_path(repo: &RepoToText, root: &Path, path: &Path) -> io::Result<()> {
+    // Resolved the same way whether given as absolute or relative, so `..` and symlinks can't
+    // reach outside the root
+    let relative = fs::canonicalize(path)?
+        .strip_prefix(root)
+        .map(Path::to_path_buf)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "outside the repository"))?;
+
+    match repo.explain(&relative)? {
+        Some(reason) => println!("{}: skipped, {}", path.display(), reason),
+        None if path.is_dir() => println!("{}: walked", path.display()),
+        None => {
+            let size = fs::metadata(path)?.len();
+            if size > repo.large_file_threshold() {
+                println!(
+                    "{}: collected, but {} is above the {} large file threshold so it is offered for deselection",
+                    path.display(),
+                    format_size(size),
+                    format_size(repo.large_file_threshold())
+                );
+            } else {
+                println!(
+                    "{}: collected ({}, ~{} tokens)",
+                    path.display(),
+                    format_size(size),
+                    estimate_tokens(size)
+                );
             }
         }
-    } else {
-        // AI is disabled and no explicit ignores
-        None
-    };
+    }
+    Ok(())
+}
 
-    let processor = RepoProcessor::new(ignore_patterns, args.include)?;
-    processor.process_repository()
+/// Prints the files a run would include, in output order, plus totals
+fn list_files(repo: &RepoToText, json: bool) -> io::Result<()> {
+    let files = repo.collect()?;
+    let total_bytes: u64 = files.iter().map(|f| f.size).sum();
+    let total_tokens: u64 = files.iter().map(|f| f.estimated_tokens).sum();
+    let mut out = io::stdout().lock();
+
+    if json {
+        let listed: Vec<serde_json::Value> = files
+            .iter()
+            .map(|f| {
+                serde_json::json!({
+                    "path": display_path(&f.path),
+                    "size": f.size,
+                    "estimated_tokens": f.estimated_tokens,
+                    "score": f.score,
+                })
+            })
+            .collect();
+        let report = serde_json::json!({
+            "files": listed,
+            "total_files": files.len(),
+            "total_bytes": total_bytes,
+            "estimated_tokens": total_tokens,
+        });
+        serde_json::to_writer_pretty(&mut out, &report)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+
+    for file in &files {
+        writeln!(
+            out,
+            "{:>10}  {:>9}  {}",
+            format_size(file.size),
+            format!("~{}", file.estimated_tokens),
+            display_path(&file.path)
+        )?;
+    }
+    writeln!(
+        out,
+        "{} files, {}, ~{} tokens",
+        files.len(),
+        format_size(total_bytes),
+        total_tokens
+    )
 }
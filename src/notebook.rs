@@ -0,0 +1,114 @@
+//! Rendering Jupyter notebooks as readable cells instead of raw JSON.
+//!
+//! Cells are written in the "percent" format understood by Jupytext and most editors: each cell
+//! starts with a `# %%` marker, and markdown and raw cells are commented out. Execution counts,
+//! metadata and image or other binary outputs are dropped.
+
+use serde_json::Value;
+
+/// A notebook rendered as source code
+pub(crate) struct RenderedNotebook {
+    pub text: String,
+    /// The kernel's language, for highlighting
+    pub language: String,
+}
+
+/// Renders a notebook's cells, or `None` if `content` isn't notebook JSON. Text outputs of code
+/// cells are included as comments when `with_outputs` is set.
+pub(crate) fn render(content: &str, with_outputs: bool) -> Option<RenderedNotebook> {
+    let notebook: Value = serde_json::from_str(content).ok()?;
+    let cells = notebook.get("cells")?.as_array()?;
+    let metadata = notebook.get("metadata");
+    let language = metadata
+        .and_then(|m| {
+            m.pointer("/language_info/name")
+                .or(m.pointer("/kernelspec/language"))
+        })
+        .and_then(Value::as_str)
+        .unwrap_or("python")
+        .to_lowercase();
+
+    let mut out = String::new();
+    for cell in cells {
+        let Some(cell_type) = cell.get("cell_type").and_then(Value::as_str) else {
+            continue;
+        };
+        let source = joined(cell.get("source"));
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        match cell_type {
+            "code" => {
+                out.push_str("# %%\n");
+                push_lines(&mut out, &source, "");
+                if with_outputs {
+                    push_outputs(&mut out, cell.get("outputs"));
+                }
+            }
+            kind => {
+                out.push_str(&format!("# %% [{}]\n", kind));
+                push_lines(&mut out, &source, "# ");
+            }
+        }
+    }
+    Some(RenderedNotebook {
+        text: out,
+        language,
+    })
+}
+
+/// Notebook text fields are either a string or a list of lines
+fn joined(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(lines)) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn push_lines(out: &mut String, text: &str, prefix: &str) {
+    for line in text.lines() {
+        if line.is_empty() {
+            out.push_str(prefix.trim_end());
+        } else {
+            out.push_str(prefix);
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+}
+
+fn push_outputs(out: &mut String, outputs: Option<&Value>) {
+    let Some(outputs) = outputs.and_then(Value::as_array) else {
+        return;
+    };
+
+    let mut texts = Vec::new();
+    for output in outputs {
+        let text = match output.get("output_type").and_then(Value::as_str) {
+            Some("stream") => joined(output.get("text")),
+            // Images and HTML are only kept when they come with a plain-text rendering
+            Some("execute_result" | "display_data") => {
+                joined(output.get("data").and_then(|data| data.get("text/plain")))
+            }
+            // The traceback is full of terminal colour codes; the error line says enough
+            Some("error") => format!(
+                "{}: {}",
+                joined(output.get("ename")),
+                joined(output.get("evalue"))
+            ),
+            _ => continue,
+        };
+        if !text.trim().is_empty() {
+            texts.push(text);
+        }
+    }
+
+    if !texts.is_empty() {
+        out.push_str("# Output:\n");
+        for text in texts {
+            push_lines(out, &text, "# ");
+        }
+    }
+}
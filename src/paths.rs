@@ -0,0 +1,164 @@
+//! Paths as written to the output: relative, with forward slashes on every OS, and with
+//! non-UTF-8 names escaped rather than mangled.
+
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// `path` with `/` between components and no leading `./`. Bytes of a name that aren't valid
+/// UTF-8 are written as `\xNN` escapes (unpaired surrogates as `\u{NNNN}` on Windows), so
+/// distinct files never share a name the way they can after a lossy conversion.
+pub fn display_path(path: &Path) -> String {
+    let mut out = String::new();
+    for component in path.components() {
+        let part = match component {
+            Component::CurDir => continue,
+            Component::RootDir => {
+                out.push('/');
+                continue;
+            }
+            Component::ParentDir => Cow::Borrowed(".."),
+            Component::Prefix(prefix) => escape(prefix.as_os_str()),
+            Component::Normal(name) => escape(name),
+        };
+        if !out.is_empty() && !out.ends_with('/') {
+            out.push('/');
+        }
+        out.push_str(&part);
+    }
+    if out.is_empty() {
+        out.push('.');
+    }
+    out
+}
+
+/// Canonicalizes a path that may not exist yet by resolving its parent
+pub fn absolute(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => fs::canonicalize(if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        })
+        .map(|parent| parent.join(name))
+        .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Whether `path` can be written without escapes
+pub(crate) fn is_utf8(path: &Path) -> bool {
+    path.to_str().is_some()
+}
+
+/// Name of the directory at `root`, after resolving `.` and symlinks
+pub fn repo_name(root: &Path) -> String {
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    match root.file_name() {
+        Some(name) => escape(name).into_owned(),
+        None => display_path(&root),
+    }
+}
+
+#[cfg(unix)]
+fn escape(name: &OsStr) -> Cow<'_, str> {
+    use std::os::unix::ffi::OsStrExt;
+
+    if let Some(name) = name.to_str() {
+        return Cow::Borrowed(name);
+    }
+    let mut out = String::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        out.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            out.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+    Cow::Owned(out)
+}
+
+#[cfg(windows)]
+fn escape(name: &OsStr) -> Cow<'_, str> {
+    use std::os::windows::ffi::OsStrExt;
+
+    if let Some(name) = name.to_str() {
+        return Cow::Borrowed(name);
+    }
+    Cow::Owned(
+        char::decode_utf16(name.encode_wide())
+            .map(|c| match c {
+                Ok(c) => c.to_string(),
+                Err(e) => format!("\\u{{{:04X}}}", e.unpaired_surrogate()),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(not(any(unix, windows)))]
+fn escape(name: &OsStr) -> Cow<'_, str> {
+    name.to_string_lossy()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_the_leading_dot_and_uses_forward_slashes() {
+        assert_eq!(display_path(Path::new("./src/main.rs")), "src/main.rs");
+        assert_eq!(display_path(Path::new("src/./lib.rs")), "src/lib.rs");
+        assert_eq!(display_path(Path::new("../other/a.rs")), "../other/a.rs");
+        assert_eq!(display_path(Path::new("/abs/a.rs")), "/abs/a.rs");
+    }
+
+    #[test]
+    fn the_root_itself_is_a_dot() {
+        assert_eq!(display_path(Path::new(".")), ".");
+        assert_eq!(display_path(Path::new("")), ".");
+    }
+
+    #[test]
+    fn utf8_names_are_unchanged() {
+        assert_eq!(
+            display_path(Path::new("docs/café/ünïcode.md")),
+            "docs/café/ünïcode.md"
+        );
+        assert!(is_utf8(Path::new("docs/café.md")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_bytes_are_escaped() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"data/caf\xe9/b\xff\xfe.csv"));
+        assert!(!is_utf8(path));
+        assert_eq!(display_path(path), "data/caf\\xE9/b\\xFF\\xFE.csv");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn valid_text_around_invalid_bytes_is_kept() {
+        use std::os::unix::ffi::OsStrExt;
+
+        // A truncated multi-byte sequence followed by a complete one
+        let name = OsStr::from_bytes(b"\xc3\xc3\xa9x");
+        assert_eq!(escape(name), "\\xC3éx");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_that_differ_only_in_invalid_bytes_stay_distinct() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let a = display_path(Path::new(OsStr::from_bytes(b"f\xfe")));
+        let b = display_path(Path::new(OsStr::from_bytes(b"f\xff")));
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,111 @@
+//! A progress bar for rendering, drawn on stderr.
+
+use dialoguer::console::{truncate_str, Term};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use repo_to_text::{display_path, format_size};
+
+/// Minimum time between redraws, so fast runs aren't slowed down by the terminal
+const REDRAW_INTERVAL: Duration = Duration::from_millis(80);
+
+const BAR_WIDTH: usize = 24;
+
+pub struct ProgressBar {
+    term: Term,
+    visible: bool,
+    total_files: usize,
+    total_bytes: u64,
+    files: AtomicUsize,
+    bytes: AtomicU64,
+    started: Instant,
+    last_draw: Mutex<Option<Instant>>,
+}
+
+impl ProgressBar {
+    /// A bar over `total_files` files of `total_bytes` bytes. It is only drawn when stderr
+    /// is a terminal.
+    pub fn new(total_files: usize, total_bytes: u64) -> Self {
+        let term = Term::stderr();
+        Self {
+            visible: term.is_term(),
+            term,
+            total_files,
+            total_bytes,
+            files: AtomicUsize::new(0),
+            bytes: AtomicU64::new(0),
+            started: Instant::now(),
+            last_draw: Mutex::new(None),
+        }
+    }
+
+    /// A bar that counts but never draws
+    pub fn hidden(total_files: usize, total_bytes: u64) -> Self {
+        Self {
+            visible: false,
+            ..Self::new(total_files, total_bytes)
+        }
+    }
+
+    /// Records one processed file of `bytes` bytes, redrawing if enough time has passed
+    pub fn inc(&self, path: &Path, bytes: u64) {
+        let files = self.files.fetch_add(1, Ordering::Relaxed) + 1;
+        let bytes = self.bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if !self.visible {
+            return;
+        }
+
+        // Whoever holds the lock is already drawing; the next file will catch up
+        let Ok(mut last_draw) = self.last_draw.try_lock() else {
+            return;
+        };
+        let now = Instant::now();
+        if last_draw.is_some_and(|last| now - last < REDRAW_INTERVAL) && files < self.total_files {
+            return;
+        }
+        *last_draw = Some(now);
+
+        let _ = self.draw(files, bytes, path);
+    }
+
+    /// Clears the bar from the terminal
+    pub fn finish(&self) {
+        if self.visible {
+            let _ = self.term.clear_line();
+        }
+    }
+
+    fn draw(&self, files: usize, bytes: u64, path: &Path) -> std::io::Result<()> {
+        let fraction = if self.total_bytes > 0 {
+            bytes as f64 / self.total_bytes as f64
+        } else {
+            files as f64 / self.total_files.max(1) as f64
+        };
+        let filled = ((fraction.min(1.0) * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+
+        let elapsed = self.started.elapsed().as_secs_f64().max(0.001);
+        let line = format!(
+            "[{}{}] {}/{} files  {}/{}  {:.0} files/s  {}",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            files,
+            self.total_files,
+            format_size(bytes),
+            format_size(self.total_bytes),
+            files as f64 / elapsed,
+            display_path(path)
+        );
+
+        // Never wrap, or clearing the line would leave the first half behind
+        let width = self.term.size().1 as usize;
+        self.term.clear_line()?;
+        self.term
+            .write_str(&truncate_str(&line, width.saturating_sub(1), "…"))
+    }
+}
@@ -0,0 +1,116 @@
+//! Output formats for the rendered document.
+
+use regex::Regex;
+use std::{fmt, str::FromStr};
+
+/// Layout of the rendered document
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Plain text with `--- File: ... ---` separators
+    #[default]
+    Text,
+    /// Markdown with a heading and fenced code block per file
+    Markdown,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Markdown => "markdown",
+        })
+    }
+}
+
+/// Parses `text` or `markdown`
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "markdown" => Ok(OutputFormat::Markdown),
+            other => Err(format!(
+                "unknown format \"{}\" (expected text or markdown)",
+                other
+            )),
+        }
+    }
+}
+
+/// Line number prefixes for file content
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineNumbers {
+    /// Minimum width numbers are right-aligned to; they are always wide enough for the last line
+    pub width: usize,
+    /// Written between the number and the line
+    pub separator: String,
+}
+
+impl Default for LineNumbers {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            separator: " | ".to_string(),
+        }
+    }
+}
+
+impl LineNumbers {
+    /// Prefixes each line of `content` with its number, returning the numbered content and
+    /// the length of the prefix, which is the same on every line
+    pub(crate) fn apply(&self, content: &str) -> (String, usize) {
+        let count = content.lines().count();
+        let width = self.width.max(count.max(1).to_string().len());
+
+        let mut out = String::with_capacity(content.len() + count * (width + self.separator.len()));
+        for (number, line) in content.lines().enumerate() {
+            out.push_str(&format!(
+                "{:>width$}{}{}\n",
+                number + 1,
+                self.separator,
+                line
+            ));
+        }
+        (out, width + self.separator.len())
+    }
+}
+
+/// Replaces embedded binary blobs (Python byte literals, base64/b85 decode calls) with a placeholder
+pub(crate) fn strip_binary_data(content: &str) -> String {
+    // Process content with the same patterns as the fish script
+    let binary_patterns = [
+        (
+            r#"(?s)(DATA = b""")[^"]*?(""")"#,
+            r#"$1<binary data removed>$2"#,
+        ),
+        (
+            r#"(?s)(b85decode\().*?(\))"#,
+            r#"$1"<binary data removed>"$2"#,
+        ),
+        (
+            r#"(?s)(base64\.[^(]*decode\().*?(\))"#,
+            r#"$1"<binary data removed>"$2"#,
+        ),
+    ];
+
+    binary_patterns
+        .iter()
+        .fold(content.to_string(), |acc, (pattern, replacement)| {
+            Regex::new(pattern)
+                .unwrap()
+                .replace_all(&acc, *replacement)
+                .to_string()
+        })
+}
+
+/// How much of a file is checked for NUL bytes, the same as git
+pub(crate) const BINARY_SNIFF_BYTES: u64 = 8000;
+
+/// Whether `content` looks like a binary file: a NUL byte near the start
+pub(crate) fn is_binary(content: &[u8]) -> bool {
+    content
+        .iter()
+        .take(BINARY_SNIFF_BYTES as usize)
+        .any(|&b| b == 0)
+}
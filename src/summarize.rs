@@ -0,0 +1,263 @@
+//! Summaries of large data files: the shape of the data rather than all of it.
+
+use regex::Regex;
+use serde_json::Value;
+use std::{collections::BTreeMap, path::Path};
+
+use crate::format_size;
+
+/// Extensions that can be summarised
+pub(crate) const DATA_EXTS: &[&str] = &["json", "csv", "tsv", "sql", "xml"];
+
+/// Data rows shown under a CSV header
+const CSV_SAMPLE_ROWS: usize = 5;
+
+/// Leading lines of an XML file shown as a sample
+const XML_SAMPLE_LINES: usize = 20;
+
+/// XML element names listed, most frequent first
+const XML_MAX_ELEMENTS: usize = 30;
+
+/// JSON nesting shown before collapsing to `...`
+const JSON_MAX_DEPTH: usize = 8;
+
+/// Object keys shown per level before the rest are counted
+const JSON_MAX_KEYS: usize = 50;
+
+/// Typical upper size of a summary, used to estimate its tokens before the file is read
+pub(crate) const ESTIMATED_SUMMARY_BYTES: u64 = 4 * 1024;
+
+/// Whether `path` has an extension [`summarize`] handles
+pub(crate) fn is_data_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| DATA_EXTS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+}
+
+/// Summarises `content` by extension, or `None` if it can't be (such as invalid JSON), in
+/// which case the full content should be used
+pub(crate) fn summarize(path: &Path, content: &str) -> Option<String> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    let (kind, body) = match ext.as_str() {
+        "json" => (
+            "JSON structure with types and array lengths",
+            json(content)?,
+        ),
+        "csv" => ("CSV header and first rows", delimited(content)),
+        "tsv" => ("TSV header and first rows", delimited(content)),
+        "sql" => ("SQL schema statements only", sql(content)),
+        "xml" => ("XML element counts and first lines", xml(content)),
+        _ => return None,
+    };
+
+    Some(format!(
+        "[Summary of a {} data file, {} lines: {}]\n\n{}",
+        format_size(content.len() as u64),
+        content.lines().count(),
+        kind,
+        body
+    ))
+}
+
+fn json(content: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(content).ok()?;
+    let mut out = String::new();
+    json_shape(&value, 0, &mut out);
+    out.push('\n');
+    Some(out)
+}
+
+/// Writes the type of `value`, describing arrays by their length and first element
+fn json_shape(value: &Value, depth: usize, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(_) => out.push_str("boolean"),
+        Value::Number(_) => out.push_str("number"),
+        Value::String(_) => out.push_str("string"),
+        Value::Array(items) => {
+            out.push_str(&format!("array[{}]", items.len()));
+            if let Some(first) = items.first() {
+                out.push_str(" of ");
+                json_shape(first, depth, out);
+            }
+        }
+        Value::Object(_) if depth >= JSON_MAX_DEPTH => out.push_str("{ ... }"),
+        Value::Object(map) if map.is_empty() => out.push_str("{}"),
+        Value::Object(map) => {
+            let indent = "  ".repeat(depth + 1);
+            out.push_str("{\n");
+            for (key, value) in map.iter().take(JSON_MAX_KEYS) {
+                out.push_str(&format!("{}{:?}: ", indent, key));
+                json_shape(value, depth + 1, out);
+                out.push('\n');
+            }
+            if map.len() > JSON_MAX_KEYS {
+                out.push_str(&format!(
+                    "{}... {} more keys\n",
+                    indent,
+                    map.len() - JSON_MAX_KEYS
+                ));
+            }
+            out.push_str(&"  ".repeat(depth));
+            out.push('}');
+        }
+    }
+}
+
+fn delimited(content: &str) -> String {
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return String::new();
+    };
+
+    let sample: Vec<&str> = lines.by_ref().take(CSV_SAMPLE_ROWS).collect();
+    let rows = sample.len() + lines.count();
+
+    let mut out = format!("{}\n", header);
+    for row in &sample {
+        out.push_str(row);
+        out.push('\n');
+    }
+    if rows > sample.len() {
+        out.push_str(&format!("... {} rows in total\n", rows));
+    }
+    out
+}
+
+/// Keeps `CREATE`, `ALTER`, `DROP` and `COMMENT ON` statements, dropping the data
+fn sql(content: &str) -> String {
+    const DDL: &[&str] = &["CREATE ", "ALTER ", "DROP ", "COMMENT ON "];
+
+    let mut out = String::new();
+    let mut in_statement = false;
+    let mut data_statements = 0;
+    for line in content.lines() {
+        let upper = line.trim_start().to_uppercase();
+        if !in_statement {
+            if DDL.iter().any(|keyword| upper.starts_with(keyword)) {
+                in_statement = true;
+            } else if upper.starts_with("INSERT ") || upper.starts_with("COPY ") {
+                data_statements += 1;
+            }
+        }
+        if in_statement {
+            out.push_str(line);
+            out.push('\n');
+            in_statement = !line.trim_end().ends_with(';');
+        }
+    }
+
+    if data_statements > 0 {
+        out.push_str(&format!(
+            "\n-- ... {} INSERT/COPY statements omitted\n",
+            data_statements
+        ));
+    }
+    out
+}
+
+fn xml(content: &str) -> String {
+    let tag = Regex::new(r"<([A-Za-z_][\w:.-]*)").unwrap();
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for captures in tag.captures_iter(content) {
+        *counts
+            .entry(captures.get(1).map_or("", |m| m.as_str()))
+            .or_default() += 1;
+    }
+
+    let mut by_count: Vec<(&str, usize)> = counts.into_iter().collect();
+    by_count.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    let mut out = String::from("<!-- Elements by count:\n");
+    for (name, count) in by_count.iter().take(XML_MAX_ELEMENTS) {
+        out.push_str(&format!("  {} <{}>\n", count, name));
+    }
+    if by_count.len() > XML_MAX_ELEMENTS {
+        out.push_str(&format!(
+            "  ... {} more element names\n",
+            by_count.len() - XML_MAX_ELEMENTS
+        ));
+    }
+    out.push_str("-->\n");
+    for line in content.lines().take(XML_SAMPLE_LINES) {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str("...\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sql_keeps_schema_statements_only() {
+        let dump = "\
+CREATE TABLE users (
+  id integer PRIMARY KEY,
+  name text
+);
+INSERT INTO users VALUES (1, 'a');
+INSERT INTO users VALUES (2, 'b');
+ALTER TABLE users ADD COLUMN email text;
+COPY users (id, name) FROM stdin;
+";
+        assert_eq!(
+            sql(dump),
+            "\
+CREATE TABLE users (
+  id integer PRIMARY KEY,
+  name text
+);
+ALTER TABLE users ADD COLUMN email text;
+
+-- ... 3 INSERT/COPY statements omitted
+"
+        );
+    }
+
+    #[test]
+    fn sql_keywords_are_case_insensitive() {
+        assert_eq!(
+            sql("  create index i on t (c);\ninsert into t values (1);\n")
+                .lines()
+                .next(),
+            Some("  create index i on t (c);")
+        );
+    }
+
+    #[test]
+    fn json_shows_types_and_array_lengths() {
+        let shape = json(r#"{"name": "x", "tags": ["a", "b"], "meta": {"ok": true, "n": null}}"#);
+        assert_eq!(
+            shape.as_deref(),
+            Some(
+                "{\n  \"meta\": {\n    \"n\": null\n    \"ok\": boolean\n  }\n  \"name\": string\n  \"tags\": array[2] of string\n}\n"
+            )
+        );
+        assert_eq!(json("not json"), None);
+    }
+
+    #[test]
+    fn delimited_keeps_the_header_and_first_rows() {
+        let rows: String = (1..=10).map(|n| format!("{},x\n", n)).collect();
+        let out = delimited(&format!("id,name\n{}", rows));
+        assert_eq!(
+            out,
+            "id,name\n1,x\n2,x\n3,x\n4,x\n5,x\n... 10 rows in total\n"
+        );
+    }
+
+    #[test]
+    fn xml_counts_elements() {
+        let out = xml("<a><b/><b/><c:d/></a>");
+        assert!(out.starts_with("<!-- Elements by count:\n  2 <b>\n  1 <a>\n  1 <c:d>\n-->\n"));
+    }
+
+    #[test]
+    fn only_known_extensions_are_data_files() {
+        assert!(is_data_file(Path::new("dump.SQL")));
+        assert!(!is_data_file(Path::new("main.rs")));
+        assert_eq!(summarize(Path::new("main.rs"), "fn main() {}"), None);
+    }
+}
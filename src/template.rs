@@ -0,0 +1,227 @@
+//! Output templates: the document header, the block written for each file and the footer,
+//! rendered with Jinja syntax.
+
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io};
+
+use crate::render::OutputFormat;
+
+const TEXT_HEADER: &str = "\
+Repository Content Extraction
+Generated on: {{ generated_on }}
+=================================================
+
+";
+
+const TEXT_FILE: &str = "\
+===============================================
+--- File: {{ path }} ---
+{% if metadata %}
+{{ metadata }}
+{% endif %}
+===============================================
+
+{{ content }}
+--- End of File ---
+
+===============================================
+";
+
+const MARKDOWN_HEADER: &str = "\
+# Repository Content Extraction
+
+Generated on: {{ generated_on }}
+
+";
+
+const MARKDOWN_FILE: &str = "\
+## File: {{ path }}
+
+{% if metadata %}
+{{ metadata }}
+
+{% endif %}
+{{ fence }}{{ lang }}
+{{ content }}{% if content is not endingwith \"\\n\" %}
+
+{% endif %}
+{{ fence }}
+
+";
+
+/// Names of the built-in templates, which match the [`OutputFormat`] names
+pub const BUILTIN_TEMPLATES: &[&str] = &["text", "markdown"];
+
+/// The three parts of an output template. Each is a Jinja template; see the README for the
+/// variables they can use.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputTemplate {
+    /// Written once before the files
+    pub header: String,
+    /// Written for every file
+    pub file: String,
+    /// Written once after the files
+    pub footer: String,
+}
+
+impl OutputTemplate {
+    /// The template behind an [`OutputFormat`]
+    pub fn builtin(format: OutputFormat) -> Self {
+        let (header, file) = match format {
+            OutputFormat::Text => (TEXT_HEADER, TEXT_FILE),
+            OutputFormat::Markdown => (MARKDOWN_HEADER, MARKDOWN_FILE),
+        };
+        Self {
+            header: header.to_string(),
+            file: file.to_string(),
+            footer: String::new(),
+        }
+    }
+
+    /// Looks up a built-in template by name
+    pub fn named(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "text" => Some(Self::builtin(OutputFormat::Text)),
+            "markdown" => Some(Self::builtin(OutputFormat::Markdown)),
+            _ => None,
+        }
+    }
+}
+
+/// One entry of the `files` list in the header and footer
+#[derive(Serialize)]
+pub(crate) struct FileSummary {
+    pub path: String,
+    pub language: String,
+    pub size: String,
+    pub bytes: u64,
+    pub tokens: u64,
+}
+
+/// Variables for the header and footer
+#[derive(Serialize)]
+pub(crate) struct DocumentContext {
+    pub generated_on: String,
+    pub root: String,
+    pub files: Vec<FileSummary>,
+    pub file_count: usize,
+    pub total_size: String,
+    pub estimated_tokens: u64,
+    pub tree: String,
+}
+
+/// Variables for each file's block
+#[derive(Serialize)]
+pub(crate) struct FileContext<'a> {
+    pub path: String,
+    pub language: String,
+    /// Markdown fence language
+    pub lang: String,
+    /// A backtick fence longer than any run of backticks in the content
+    pub fence: String,
+    pub content: &'a str,
+    pub size: String,
+    pub bytes: u64,
+    pub lines: usize,
+    pub tokens: u64,
+    pub metadata: Option<String>,
+}
+
+/// An [`OutputTemplate`], compiled
+pub(crate) struct Templates {
+    env: Environment<'static>,
+}
+
+impl Templates {
+    pub fn new(template: &OutputTemplate) -> io::Result<Self> {
+        let mut env = Environment::new();
+        env.set_keep_trailing_newline(true);
+        env.set_trim_blocks(true);
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+
+        for (name, source) in [
+            ("header", &template.header),
+            ("file", &template.file),
+            ("footer", &template.footer),
+        ] {
+            env.add_template_owned(name, source.clone())
+                .map_err(|e| template_error(name, e))?;
+        }
+        Ok(Self { env })
+    }
+
+    pub fn render_header(&self, context: &DocumentContext) -> io::Result<String> {
+        self.render("header", context)
+    }
+
+    pub fn render_footer(&self, context: &DocumentContext) -> io::Result<String> {
+        self.render("footer", context)
+    }
+
+    pub fn render_file(&self, context: &FileContext) -> io::Result<String> {
+        self.render("file", context)
+    }
+
+    fn render<S: Serialize>(&self, name: &'static str, context: S) -> io::Result<String> {
+        self.env
+            .get_template(name)
+            .and_then(|template| template.render(context))
+            .map_err(|e| template_error(name, e))
+    }
+}
+
+fn template_error(part: &str, e: minijinja::Error) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Invalid {} template: {}", part, e),
+    )
+}
+
+/// A backtick fence longer than any backtick run inside `content`
+pub(crate) fn fence_for(content: &str) -> String {
+    let longest_run = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest_run.max(2) + 1)
+}
+
+/// Draws `paths`, which use `/` as the separator, as an indented tree, directories first at
+/// each level
+pub(crate) fn tree(paths: &[String]) -> String {
+    #[derive(Default)]
+    struct Node {
+        dirs: BTreeMap<String, Node>,
+        files: Vec<String>,
+    }
+
+    let mut root = Node::default();
+    for path in paths {
+        let mut parts: Vec<&str> = path.split('/').collect();
+        let Some(file) = parts.pop() else {
+            continue;
+        };
+        let mut node = &mut root;
+        for dir in parts {
+            node = node.dirs.entry(dir.to_string()).or_default();
+        }
+        node.files.push(file.to_string());
+    }
+
+    fn draw(node: &Node, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+        for (name, child) in &node.dirs {
+            out.push_str(&format!("{}{}/\n", indent, name));
+            draw(child, depth + 1, out);
+        }
+        let mut files = node.files.clone();
+        files.sort();
+        for file in files {
+            out.push_str(&format!("{}{}\n", indent, file));
+        }
+    }
+
+    let mut out = String::new();
+    draw(&root, 0, &mut out);
+    out
+}
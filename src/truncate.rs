@@ -0,0 +1,311 @@
+//! Cutting oversized files down to size, with a marker where content was left out.
+
+use regex::Regex;
+use std::{fmt, str::FromStr, sync::OnceLock};
+
+use crate::estimate_tokens;
+
+const DEFAULT_LINES: usize = 200;
+const DEFAULT_TOKENS: usize = 2000;
+
+/// How to cut down a file over the size limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Truncation {
+    /// The first N lines
+    Head(usize),
+    /// The first and last N lines
+    HeadTail(usize),
+    /// The first N tokens, estimated at about 4 bytes each
+    Tokens(usize),
+    /// The first N lines, then the declarations found in the rest
+    Outline(usize),
+}
+
+impl Default for Truncation {
+    fn default() -> Self {
+        Truncation::Head(DEFAULT_LINES)
+    }
+}
+
+impl fmt::Display for Truncation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Truncation::Head(lines) => write!(f, "head:{}", lines),
+            Truncation::HeadTail(lines) => write!(f, "head-tail:{}", lines),
+            Truncation::Tokens(tokens) => write!(f, "tokens:{}", tokens),
+            Truncation::Outline(lines) => write!(f, "outline:{}", lines),
+        }
+    }
+}
+
+/// Parses `MODE[:N]`, such as `head`, `head-tail:50` or `tokens:4000`
+impl FromStr for Truncation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mode, count) = match s.split_once(':') {
+            Some((mode, count)) => {
+                let count = count
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .ok_or_else(|| {
+                        format!("invalid count \"{}\" (expected a positive number)", count)
+                    })?;
+                (mode, Some(count))
+            }
+            None => (s, None),
+        };
+
+        match mode.trim().to_lowercase().as_str() {
+            "head" => Ok(Truncation::Head(count.unwrap_or(DEFAULT_LINES))),
+            "head-tail" => Ok(Truncation::HeadTail(count.unwrap_or(DEFAULT_LINES / 2))),
+            "tokens" => Ok(Truncation::Tokens(count.unwrap_or(DEFAULT_TOKENS))),
+            "outline" => Ok(Truncation::Outline(count.unwrap_or(DEFAULT_LINES / 4))),
+            other => Err(format!(
+                "unknown truncation mode \"{}\" (expected head, head-tail, tokens or outline)",
+                other
+            )),
+        }
+    }
+}
+
+/// Cuts `content` down as `truncation` says, then to at most `max_bytes` whatever the mode, so
+/// a file of a few enormous lines can't slip through a line-based mode. Content that already
+/// fits is returned unchanged. `number_prefix` is the length of the line number prefix already
+/// on every line, if any, so the outline can use those numbers rather than adding its own.
+pub(crate) fn truncate(
+    content: &str,
+    truncation: Truncation,
+    number_prefix: usize,
+    max_bytes: usize,
+) -> String {
+    cap_bytes(by_mode(content, truncation, number_prefix), max_bytes)
+}
+
+fn by_mode(content: &str, truncation: Truncation, number_prefix: usize) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    match truncation {
+        Truncation::Head(keep) => {
+            if lines.len() <= keep {
+                return content.to_string();
+            }
+            let mut out = joined(&lines[..keep]);
+            out.push_str(&lines_marker(lines.len() - keep));
+            out
+        }
+        Truncation::HeadTail(keep) => {
+            if lines.len() <= keep * 2 {
+                return content.to_string();
+            }
+            let mut out = joined(&lines[..keep]);
+            out.push_str(&lines_marker(lines.len() - keep * 2));
+            out.push_str(&joined(&lines[lines.len() - keep..]));
+            out
+        }
+        Truncation::Tokens(keep) => {
+            let mut end = keep.saturating_mul(4);
+            if end >= content.len() {
+                return content.to_string();
+            }
+            end = line_cut(content, end);
+            let mut out = content[..end].to_string();
+            if !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str(&format!(
+                "[... ~{} tokens truncated ...]\n",
+                with_separators(estimate_tokens((content.len() - end) as u64) as usize)
+            ));
+            out
+        }
+        Truncation::Outline(keep) => {
+            if lines.len() <= keep {
+                return content.to_string();
+            }
+            let mut out = joined(&lines[..keep]);
+            out.push_str(&format!(
+                "[... {} lines truncated, declarations in them follow ...]\n",
+                with_separators(lines.len() - keep)
+            ));
+            for (number, line) in lines.iter().enumerate().skip(keep) {
+                let code = line.get(number_prefix..).unwrap_or_default();
+                if !declaration().is_match(code) {
+                    continue;
+                }
+                if number_prefix > 0 {
+                    out.push_str(line.trim_end());
+                    out.push('\n');
+                } else {
+                    out.push_str(&format!("{:>6}: {}\n", number + 1, line.trim_end()));
+                }
+            }
+            out
+        }
+    }
+}
+
+/// Cuts `content` to at most `max_bytes`, with a marker saying how much was left out
+fn cap_bytes(content: String, max_bytes: usize) -> String {
+    if content.len() <= max_bytes {
+        return content;
+    }
+    let end = line_cut(&content, max_bytes);
+    let mut out = content[..end].to_string();
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&format!(
+        "[... {} more bytes truncated ...]\n",
+        with_separators(content.len() - end)
+    ));
+    out
+}
+
+/// Where to cut `content` to keep at most `max_bytes`: after the last whole line that fits, or
+/// at a character boundary when not even the first line fits
+fn line_cut(content: &str, max_bytes: usize) -> usize {
+    let mut end = max_bytes.min(content.len());
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    match content[..end].rfind('\n') {
+        Some(newline) => newline + 1,
+        None => end,
+    }
+}
+
+fn joined(lines: &[&str]) -> String {
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+fn lines_marker(count: usize) -> String {
+    format!("[... {} lines truncated ...]\n", with_separators(count))
+}
+
+/// Lines starting a function, type or module in common languages
+fn declaration() -> &'static Regex {
+    static DECLARATION: OnceLock<Regex> = OnceLock::new();
+    DECLARATION.get_or_init(|| {
+        Regex::new(
+            r"^\s*(?:(?:pub(?:\([^)]*\))?|export|default|public|private|protected|internal|static|abstract|final|async|unsafe|extern|const)\s+)*(?:fn|struct|enum|trait|impl|mod|type|union|class|interface|def|function|func|module|namespace|object)\b",
+        )
+        .unwrap()
+    })
+}
+
+/// Formats `n` with thousands separators, like `12,345`
+fn with_separators(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && i % 3 == digits.len() % 3 {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(count: usize) -> String {
+        (1..=count).map(|n| format!("line {}\n", n)).collect()
+    }
+
+    #[test]
+    fn parses_modes_with_and_without_counts() {
+        assert_eq!("head".parse(), Ok(Truncation::Head(200)));
+        assert_eq!("head-tail:50".parse(), Ok(Truncation::HeadTail(50)));
+        assert_eq!("TOKENS:4000".parse(), Ok(Truncation::Tokens(4000)));
+        assert_eq!("outline".parse(), Ok(Truncation::Outline(50)));
+        assert!("head:0".parse::<Truncation>().is_err());
+        assert!("middle".parse::<Truncation>().is_err());
+        assert_eq!(Truncation::HeadTail(7).to_string(), "head-tail:7");
+    }
+
+    #[test]
+    fn head_keeps_the_first_lines() {
+        let out = truncate(&numbered(10), Truncation::Head(3), 0, usize::MAX);
+        assert_eq!(out, "line 1\nline 2\nline 3\n[... 7 lines truncated ...]\n");
+    }
+
+    #[test]
+    fn head_tail_keeps_both_ends() {
+        let out = truncate(&numbered(10), Truncation::HeadTail(2), 0, usize::MAX);
+        assert_eq!(
+            out,
+            "line 1\nline 2\n[... 6 lines truncated ...]\nline 9\nline 10\n"
+        );
+    }
+
+    #[test]
+    fn content_that_fits_is_unchanged() {
+        let content = numbered(5);
+        for truncation in [
+            Truncation::Head(5),
+            Truncation::HeadTail(3),
+            Truncation::Tokens(100),
+            Truncation::Outline(5),
+        ] {
+            assert_eq!(truncate(&content, truncation, 0, 1000), content);
+        }
+    }
+
+    #[test]
+    fn line_modes_still_cap_the_bytes() {
+        let content = format!("{}\n{}\n", "a".repeat(5000), "b".repeat(5000));
+        for truncation in [
+            Truncation::Head(200),
+            Truncation::HeadTail(100),
+            Truncation::Outline(50),
+        ] {
+            let out = truncate(&content, truncation, 0, 6000);
+            assert!(out.starts_with(&"a".repeat(5000)), "{}", truncation);
+            assert!(out.ends_with("[... 5,001 more bytes truncated ...]\n"));
+            assert!(out.len() < 6000);
+        }
+    }
+
+    #[test]
+    fn byte_cap_cuts_a_single_long_line_on_a_char_boundary() {
+        let content = "é".repeat(100);
+        let out = truncate(&content, Truncation::Head(10), 0, 11);
+        assert_eq!(
+            out,
+            format!("{}\n[... 190 more bytes truncated ...]\n", "é".repeat(5))
+        );
+    }
+
+    #[test]
+    fn tokens_cut_at_a_line_end() {
+        let content = "  1 | first line\n  2 | second line\n  3 | third\n";
+        let out = truncate(content, Truncation::Tokens(6), 6, usize::MAX);
+        assert_eq!(out, "  1 | first line\n[... ~8 tokens truncated ...]\n");
+    }
+
+    #[test]
+    fn outline_lists_declarations_after_the_head() {
+        let content = "use std::io;\n\nfn main() {\n    run();\n}\n\npub(crate) struct Config;\n";
+        let out = truncate(content, Truncation::Outline(1), 0, usize::MAX);
+        assert_eq!(
+            out,
+            "use std::io;\n\
+             [... 6 lines truncated, declarations in them follow ...]\n     \
+             3: fn main() {\n     \
+             7: pub(crate) struct Config;\n"
+        );
+    }
+
+    #[test]
+    fn separates_thousands() {
+        assert_eq!(with_separators(7), "7");
+        assert_eq!(with_separators(1234), "1,234");
+        assert_eq!(with_separators(1234567), "1,234,567");
+    }
+}
@@ -0,0 +1,140 @@
+//! Watches a repository for changes to files that would be collected.
+
+use notify::{event::ModifyKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use walkdir::WalkDir;
+
+use repo_to_text::{absolute, RepoToText};
+
+/// How long the tree must be quiet before a burst of changes is reported
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+pub struct RepoWatcher {
+    // Dropping the watcher stops the notifications
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    root: PathBuf,
+    ignored: Vec<PathBuf>,
+}
+
+impl RepoWatcher {
+    /// Starts watching the directories under `root` that `repo` walks into, so pruned trees
+    /// such as `node_modules` don't use up the system's watches. Changes to any path in
+    /// `ignored`, such as the output file, are never reported.
+    pub fn new(root: &Path, ignored: &[PathBuf], repo: &RepoToText) -> io::Result<Self> {
+        let root = fs::canonicalize(root)?;
+        let (tx, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(tx).map_err(io::Error::other)?;
+
+        let mut watcher = Self {
+            watcher,
+            events,
+            root: root.clone(),
+            ignored: ignored.iter().map(|path| absolute(path)).collect(),
+        };
+        watcher.watch_tree(&root, repo).map_err(io::Error::other)?;
+        Ok(watcher)
+    }
+
+    /// Watches `dir` and every directory below it that `repo` walks into, returning the
+    /// collected files found on the way, relative to the root
+    fn watch_tree(&mut self, dir: &Path, repo: &RepoToText) -> notify::Result<Vec<PathBuf>> {
+        let root = self.root.clone();
+        let walker = WalkDir::new(dir).into_iter().filter_entry(|entry| {
+            !entry.file_type().is_dir()
+                || entry
+                    .path()
+                    .strip_prefix(&root)
+                    .is_ok_and(|relative| repo.walks_dir(relative))
+        });
+
+        let mut files = Vec::new();
+        for entry in walker.filter_map(Result::ok) {
+            let Ok(relative) = entry.path().strip_prefix(&root) else {
+                continue;
+            };
+            if entry.file_type().is_dir() {
+                self.watcher
+                    .watch(entry.path(), RecursiveMode::NonRecursive)?;
+            } else if repo.matches_filters(relative) {
+                files.push(relative.to_path_buf());
+            }
+        }
+        Ok(files)
+    }
+
+    /// Blocks until a file that `repo` would collect changes, then waits for the burst of
+    /// changes to settle. Returns the changed paths, relative to the root.
+    pub fn wait(&mut self, repo: &RepoToText) -> io::Result<Vec<PathBuf>> {
+        let mut changed = BTreeSet::new();
+
+        while changed.is_empty() {
+            let event = self
+                .events
+                .recv()
+                .map_err(|_| io::Error::other("file watcher stopped"))?;
+            self.record(event, repo, &mut changed);
+        }
+
+        loop {
+            match self.events.recv_timeout(DEBOUNCE) {
+                Ok(event) => self.record(event, repo, &mut changed),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::other("file watcher stopped"))
+                }
+            }
+        }
+
+        Ok(changed.into_iter().collect())
+    }
+
+    fn record(
+        &mut self,
+        event: notify::Result<Event>,
+        repo: &RepoToText,
+        changed: &mut BTreeSet<PathBuf>,
+    ) {
+        let Ok(event) = event else {
+            return;
+        };
+
+        // Reads (including our own while rendering) are not changes
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+
+        let added = matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+        );
+        for path in event.paths {
+            if self.ignored.contains(&path) {
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(&self.root) else {
+                continue;
+            };
+            // A new or moved-in directory needs its own watches, and its files are new too.
+            // Failing to watch it (it may already be gone again) only misses its changes.
+            if path.is_dir() {
+                if added && repo.walks_dir(relative) {
+                    if let Ok(files) = self.watch_tree(&path, repo) {
+                        changed.extend(files);
+                    }
+                }
+                continue;
+            }
+            if repo.matches_filters(relative) {
+                changed.insert(relative.to_path_buf());
+            }
+        }
+    }
+}